@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::moves::{Move, Position};
+use crate::pgn::{Pgn, PgnError};
+use crate::piece::{Color, Kind};
+use crate::polyglot;
+use crate::table::Board;
+
+/// An entry of a Polyglot book: a move for the position with the given
+/// key, and how often it should be played relative to the other ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    /// The move as encoded by Polyglot, see [`Entry::r#move`].
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// A move found in a book for a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookMove {
+    pub r#move: Move,
+    pub weight: u16,
+}
+
+/// How a move is picked among the ones the book has for a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Selection {
+    /// At random, in proportion to the weights.
+    #[default]
+    Weighted,
+    /// Always the one with the highest weight.
+    Best,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "weighted" => Ok(Selection::Weighted),
+            "best" => Ok(Selection::Best),
+            _ => Err(format!(
+                "unknown book selection {s:?}, expected `weighted` or `best`"
+            )),
+        }
+    }
+}
+
+/// An opening book in the Polyglot `.bin` format.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    /// Sorted by key, so the moves of a position can be found by binary search.
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    /// Reads the book, a sequence of 16 byte big-endian entries.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut entries: Vec<_> = bytes
+            .chunks_exact(16)
+            .map(|chunk| {
+                let be = |range: std::ops::Range<usize>| {
+                    chunk[range]
+                        .iter()
+                        .fold(0u64, |acc, &byte| acc << 8 | byte as u64)
+                };
+                Entry {
+                    key: be(0..8),
+                    raw_move: be(8..10) as u16,
+                    weight: be(10..12) as u16,
+                    learn: be(12..16) as u32,
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Book::from_bytes(&std::fs::read(path)?))
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Writes the book in the Polyglot format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries
+            .iter()
+            .flat_map(|entry| {
+                let mut bytes = [0; 16];
+                bytes[0..8].copy_from_slice(&entry.key.to_be_bytes());
+                bytes[8..10].copy_from_slice(&entry.raw_move.to_be_bytes());
+                bytes[10..12].copy_from_slice(&entry.weight.to_be_bytes());
+                bytes[12..16].copy_from_slice(&entry.learn.to_be_bytes());
+                bytes
+            })
+            .collect()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// The legal moves the book has for the position, best first.
+    pub fn moves(&self, board: &Board, turn: Color) -> Vec<BookMove> {
+        let key = polyglot::key(board, turn);
+        let start = self
+            .entries
+            .partition_point(|entry| entry.key < key);
+        let mut moves: Vec<_> = self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| BookMove {
+                r#move: entry.r#move(board),
+                weight: entry.weight,
+            })
+            .filter(|book_move| {
+                board
+                    .legal_moves(turn)
+                    .any(|r#move| r#move == book_move.r#move)
+            })
+            .collect();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        moves
+    }
+
+    /// Picks a move for the position, if the book has any.
+    pub fn choose(
+        &self,
+        board: &Board,
+        turn: Color,
+        selection: Selection,
+        rng: &mut Rng,
+    ) -> Option<Move> {
+        let moves = self.moves(board, turn);
+        let total: u64 = moves
+            .iter()
+            .map(|book_move| book_move.weight as u64)
+            .sum();
+        if selection == Selection::Best || total == 0 {
+            return moves
+                .first()
+                .map(|book_move| book_move.r#move);
+        }
+        let mut pick = rng.next_u64() % total;
+        moves
+            .iter()
+            .find(|book_move| {
+                let found = pick < book_move.weight as u64;
+                pick = pick.saturating_sub(book_move.weight as u64);
+                found
+            })
+            .map(|book_move| book_move.r#move)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BuildOptions {
+    /// Only the moves of the first plies of each game are counted.
+    pub max_ply: usize,
+    /// Moves played fewer times than this are left out of the book.
+    pub min_count: u32,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            max_ply: 20,
+            min_count: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    count: u32,
+    /// Two points for each win of the side that played the move, one
+    /// for each draw, as in the books made by Polyglot.
+    points: u64,
+}
+
+/// Counts the moves played in each position of a collection of games.
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    moves: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    /// Adds the moves of the first plies of a game. Games without a
+    /// result count as draws.
+    pub fn add_game(&mut self, game: &Pgn, options: BuildOptions) -> Result<(), PgnError> {
+        let winner = match game.result.as_deref() {
+            Some("1-0") => Some(Color::White),
+            Some("0-1") => Some(Color::Black),
+            _ => None,
+        };
+        let plies = game.plies()?;
+        for pair in plies
+            .windows(2)
+            .take(options.max_ply)
+        {
+            let (ply, next) = (pair[0], pair[1]);
+            let Some(r#move) = next.last_move else {
+                continue;
+            };
+            let key = polyglot::key(&ply.board, ply.turn);
+            let stats = self
+                .moves
+                .entry((key, Entry::encode(&ply.board, r#move)))
+                .or_default();
+            stats.count += 1;
+            stats.points += match winner {
+                Some(color) if color == ply.turn => 2,
+                Some(_) => 0,
+                None => 1,
+            };
+        }
+        Ok(())
+    }
+
+    /// Builds the book, scaling the weights down if needed so they fit in
+    /// the 16 bits of a Polyglot entry.
+    pub fn finish(self, options: BuildOptions) -> Book {
+        let kept: Vec<_> = self
+            .moves
+            .into_iter()
+            .filter(|(_, stats)| stats.count >= options.min_count)
+            .collect();
+        let max = kept
+            .iter()
+            .map(|(_, stats)| stats.points)
+            .max()
+            .unwrap_or_default();
+        let scale = |points: u64| {
+            if max <= u16::MAX as u64 {
+                points as u16
+            } else {
+                // moves with some points keep at least one
+                (points * u16::MAX as u64 / max).max(points.min(1)) as u16
+            }
+        };
+        let mut entries: Vec<_> = kept
+            .into_iter()
+            .map(|((key, raw_move), stats)| Entry {
+                key,
+                raw_move,
+                weight: scale(stats.points),
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.raw_move));
+        Book { entries }
+    }
+}
+
+impl Entry {
+    /// Encodes a move played in `board` the way Polyglot does, with
+    /// castling written as the king capturing its own rook.
+    pub fn encode(board: &Board, r#move: Move) -> u16 {
+        let square = |pos: Position| polyglot::square(pos) as u16;
+        let king = board[r#move.from].filter(|piece| piece.kind == Kind::King);
+        let castles = king.is_some() && (r#move.to.file - r#move.from.file).abs() == 2;
+        let to = match r#move.to.file {
+            6 if castles => Position {
+                rank: r#move.to.rank,
+                file: 7,
+            },
+            2 if castles => Position {
+                rank: r#move.to.rank,
+                file: 0,
+            },
+            _ => r#move.to,
+        };
+        let promotion = match r#move.promotion {
+            Some(Kind::Knight) => 1,
+            Some(Kind::Bishop) => 2,
+            Some(Kind::Rook) => 3,
+            Some(Kind::Queen) => 4,
+            _ => 0,
+        };
+        promotion << 12 | square(r#move.from) << 6 | square(to)
+    }
+
+    /// Decodes the move. Polyglot writes castling as the king capturing
+    /// its own rook, which is turned into the usual two square king move.
+    pub fn r#move(&self, board: &Board) -> Move {
+        let square = |index: u16| Position {
+            rank: 7 - (index >> 3 & 7) as isize,
+            file: (index & 7) as isize,
+        };
+        let to = square(self.raw_move);
+        let from = square(self.raw_move >> 6);
+        let promotion = match self.raw_move >> 12 & 7 {
+            1 => Some(Kind::Knight),
+            2 => Some(Kind::Bishop),
+            3 => Some(Kind::Rook),
+            4 => Some(Kind::Queen),
+            _ => None,
+        };
+        let king = board[from].filter(|piece| piece.kind == Kind::King);
+        let castles = king.is_some()
+            && from.file == 4
+            && (to.file == 0 || to.file == 7)
+            && from.rank == to.rank;
+        let to = if castles {
+            Position {
+                rank: to.rank,
+                file: if to.file == 7 { 6 } else { 2 },
+            }
+        } else {
+            to
+        };
+        Move {
+            to,
+            from,
+            promotion,
+        }
+    }
+}
+
+/// A small xorshift generator, enough to pick book moves at random.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    /// Seeds the generator from the clock.
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+fn entry(key: u64, from: &str, to: &str, weight: u16) -> Entry {
+    let square = |s: &str| polyglot::square(s.parse().unwrap()) as u16;
+    Entry {
+        key,
+        raw_move: square(from) << 6 | square(to),
+        weight,
+        learn: 0,
+    }
+}
+
+#[test]
+fn book_moves() {
+    let start = polyglot::key(&Board::default(), Color::White);
+    let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    let (board, turn) = Board::from_fen(castling).unwrap();
+    let castles = polyglot::key(&board, turn);
+    let entries = vec![
+        entry(start, "e2", "e4", 10),
+        entry(start, "d2", "d4", 30),
+        // not legal, so it is skipped
+        entry(start, "e2", "e5", 50),
+        entry(castles, "e1", "h1", 1),
+    ];
+    let book = Book::from_bytes(&Book { entries }.to_bytes());
+    let moves: Vec<_> = book
+        .moves(&Board::default(), Color::White)
+        .iter()
+        .map(|book_move| book_move.r#move.to_string())
+        .collect();
+    assert_eq!(moves, ["d2d4", "e2e4"]);
+    let mut rng = Rng::new(7);
+    let best = book.choose(&Board::default(), Color::White, Selection::Best, &mut rng);
+    assert_eq!(best.map(|m| m.to_string()), Some("d2d4".into()));
+    assert_eq!(
+        book.choose(&board, turn, Selection::Weighted, &mut rng)
+            .map(|m| m.to_string()),
+        Some("e1g1".into())
+    );
+}
+
+#[test]
+fn build_book() {
+    let games = crate::pgn::parse(
+        "1. e4 e5 2. Nf3 1-0\n\n1. e4 c5 0-1\n\n1. e4 e5 1/2-1/2\n\n1. d4 d5 *\n",
+    );
+    let options = BuildOptions {
+        max_ply: 1,
+        min_count: 2,
+    };
+    let mut builder = BookBuilder::default();
+    for game in &games {
+        builder
+            .add_game(game, options)
+            .unwrap();
+    }
+    let book = builder.finish(options);
+    // only the first ply counts, and d4 was played once
+    let moves: Vec<_> = book
+        .moves(&Board::default(), Color::White)
+        .iter()
+        .map(|book_move| (book_move.r#move.to_string(), book_move.weight))
+        .collect();
+    // e4 won once, lost once and drew once
+    assert_eq!(moves, [("e2e4".to_string(), 3)]);
+
+    let (board, turn) = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let castle = board
+        .parse_san("O-O-O", turn)
+        .unwrap();
+    let raw_move = Entry::encode(&board, castle);
+    let entry = Entry {
+        key: 0,
+        raw_move,
+        weight: 1,
+        learn: 0,
+    };
+    assert_eq!(entry.r#move(&board), castle);
+}
@@ -0,0 +1,305 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::moves::Move;
+
+/// The time each side has for the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    /// The time on the clock at the start, and added again after every
+    /// `moves` moves.
+    pub base: Duration,
+    /// The time added after each move.
+    pub increment: Duration,
+    /// The number of moves of each period, `None` for sudden death.
+    pub moves: Option<u32>,
+    /// The time at the start of each move that is not taken from the
+    /// clock.
+    pub delay: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControlError(String);
+
+impl Display for TimeControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid time control {:?}, expected [moves/]seconds[+increment][d delay], as in 40/300+2",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for TimeControlError {}
+
+/// Parses time controls like `300+2`, `40/5400` or `60d2`, with the
+/// times in seconds.
+impl FromStr for TimeControl {
+    type Err = TimeControlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || TimeControlError(s.to_string());
+        let seconds = |text: &str| {
+            text.parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map(Duration::from_secs_f64)
+                .ok_or_else(error)
+        };
+        let (moves, rest) = match s.split_once('/') {
+            Some((moves, rest)) => (
+                Some(
+                    moves
+                        .parse()
+                        .map_err(|_| error())?,
+                ),
+                rest,
+            ),
+            None => (None, s),
+        };
+        let (rest, delay) = match rest.split_once('d') {
+            Some((rest, delay)) => (rest, seconds(delay)?),
+            None => (rest, Duration::ZERO),
+        };
+        let (base, increment) = match rest.split_once('+') {
+            Some((base, increment)) => (seconds(base)?, seconds(increment)?),
+            None => (seconds(rest)?, Duration::ZERO),
+        };
+        if moves == Some(0) {
+            return Err(error());
+        }
+        Ok(TimeControl {
+            base,
+            increment,
+            moves,
+            delay,
+        })
+    }
+}
+
+/// The clock of one side.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    pub control: TimeControl,
+    remaining: Duration,
+    /// The moves left until the next period, `None` for sudden death.
+    moves_to_go: Option<u32>,
+    flagged: bool,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Clock {
+            control,
+            remaining: control.base,
+            moves_to_go: control.moves,
+            flagged: false,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    pub fn moves_to_go(&self) -> Option<u32> {
+        self.moves_to_go
+    }
+
+    /// Whether the side ran out of time.
+    pub fn flagged(&self) -> bool {
+        self.flagged
+    }
+
+    /// Takes the time a move took from the clock, then adds the increment
+    /// and the time of the next period if it starts.
+    pub fn punch(&mut self, elapsed: Duration) {
+        let used = elapsed.saturating_sub(self.control.delay);
+        if used > self.remaining {
+            self.remaining = Duration::ZERO;
+            self.flagged = true;
+            return;
+        }
+        self.remaining = self.remaining - used + self.control.increment;
+        if let Some(moves) = &mut self.moves_to_go {
+            *moves -= 1;
+            if *moves == 0 {
+                self.remaining += self.control.base;
+                self.moves_to_go = self.control.moves;
+            }
+        }
+    }
+
+    /// How long the engine may think about its next move.
+    pub fn allocate(&self) -> Allocation {
+        allocate(
+            self.remaining,
+            self.moves_to_go,
+            self.control.increment,
+            self.control.delay,
+        )
+    }
+}
+
+/// How long to think about a move with `remaining` time on the clock,
+/// `moves_to_go` moves until the next period, if any, and the given
+/// increment and delay.
+pub fn allocate(
+    remaining: Duration,
+    moves_to_go: Option<u32>,
+    increment: Duration,
+    delay: Duration,
+) -> Allocation {
+    // the time lost between the clock and the search
+    const OVERHEAD: Duration = Duration::from_millis(30);
+    // sudden death games are planned as if this many moves were left
+    const MOVES: u32 = 30;
+    let available = remaining.saturating_sub(OVERHEAD);
+    let moves = moves_to_go
+        .unwrap_or(MOVES)
+        .clamp(1, MOVES);
+    // the increment of this move is only added once it is made,
+    // while the delay is never taken from the clock
+    let maximum = if moves == 1 {
+        available.mul_f64(0.9)
+    } else {
+        available / 3
+    } + delay;
+    let optimum = available / moves + increment * 3 / 4 + delay;
+    Allocation {
+        optimum: optimum.min(maximum),
+        maximum,
+    }
+}
+
+/// Shows the remaining time as `m:ss.s`.
+impl Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tenths = self.remaining.as_millis() / 100;
+        write!(
+            f,
+            "{}:{:02}.{}",
+            tenths / 600,
+            tenths / 10 % 60,
+            tenths % 10
+        )
+    }
+}
+
+/// The thinking time of a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    /// The time the search usually takes, after which it does not start
+    /// a new iteration.
+    pub optimum: Duration,
+    /// The search is stopped after this time, whatever happens.
+    pub maximum: Duration,
+}
+
+/// Decides after each iteration of the search whether to start another
+/// one. The optimum time is extended while the best move keeps changing
+/// or the score drops, up to the maximum time.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    allocation: Allocation,
+    start: Instant,
+    last: Option<(Option<Move>, f32)>,
+    /// How much the optimum time is stretched, from 1 when the search is
+    /// stable.
+    scale: f32,
+}
+
+impl TimeManager {
+    pub fn new(allocation: Allocation, start: Instant) -> Self {
+        TimeManager {
+            allocation,
+            start,
+            last: None,
+            scale: 1.0,
+        }
+    }
+
+    /// When the search has to stop.
+    pub fn deadline(&self) -> Instant {
+        self.start + self.allocation.maximum
+    }
+
+    /// Records the result of an iteration and returns whether there is
+    /// time for another one.
+    pub fn next_iteration(&mut self, best: Option<Move>, score: f32) -> bool {
+        // stability is regained slowly
+        self.scale = (self.scale * 0.9).max(1.0);
+        if let Some((last_best, last_score)) = self.last {
+            if last_best != best {
+                self.scale += 0.5;
+            }
+            if score < last_score - 0.3 {
+                self.scale += 0.5;
+            }
+        }
+        self.scale = self.scale.min(3.0);
+        self.last = Some((best, score));
+        let optimum = self
+            .allocation
+            .optimum
+            .mul_f32(self.scale)
+            .min(self.allocation.maximum);
+        // an iteration usually takes longer than all the previous ones
+        self.start.elapsed() < optimum / 2
+    }
+}
+
+#[test]
+fn parse_time_controls() {
+    let control: TimeControl = "40/300+2.5d1"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        control,
+        TimeControl {
+            base: Duration::from_secs(300),
+            increment: Duration::from_millis(2500),
+            moves: Some(40),
+            delay: Duration::from_secs(1),
+        }
+    );
+    assert_eq!(
+        "60".parse::<TimeControl>()
+            .unwrap()
+            .moves,
+        None
+    );
+    assert!("0/60"
+        .parse::<TimeControl>()
+        .is_err());
+    assert!("fast"
+        .parse::<TimeControl>()
+        .is_err());
+}
+
+#[test]
+fn clock_periods() {
+    let mut clock = Clock::new("2/60+1d2".parse().unwrap());
+    clock.punch(Duration::from_secs(12));
+    assert_eq!(clock.remaining(), Duration::from_secs(51));
+    assert_eq!(clock.to_string(), "0:51.0");
+    // the second move starts a new period
+    clock.punch(Duration::from_secs(1));
+    assert_eq!(clock.remaining(), Duration::from_secs(112));
+    assert_eq!(clock.moves_to_go(), Some(2));
+    assert!(!clock.flagged());
+    clock.punch(Duration::from_secs(200));
+    assert!(clock.flagged());
+
+    // the engine never uses more than what is left
+    for control in ["1/0.5", "5+0", "40/120", "1+3d1"] {
+        let mut clock = Clock::new(control.parse().unwrap());
+        for _ in 0..200 {
+            let allocation = clock.allocate();
+            assert!(allocation.optimum <= allocation.maximum);
+            clock.punch(allocation.maximum);
+            assert!(!clock.flagged(), "{control}");
+        }
+    }
+}
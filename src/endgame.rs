@@ -0,0 +1,432 @@
+use once_cell::sync::Lazy;
+
+use crate::eval::material;
+use crate::moves::Position;
+use crate::piece::{Color, Kind};
+use crate::table::Board;
+
+/// The score of an ending that is known to be won, before the bonuses
+/// that lead the search towards the win. It is above any evaluation of
+/// the material left, and below the mates found by the search.
+pub const KNOWN_WIN: f32 = 100.0;
+
+/// The score of positions whose outcome is known, from the point of view
+/// of `turn`, to be used in place of the evaluation. These are king and
+/// pawn against king, the mates with a queen, a rook or a bishop and a
+/// knight against a lone king, and a rook pawn with the wrong bishop.
+/// `None` as well for positions without both kings.
+pub fn evaluate(board: &Board, turn: Color) -> Option<f32> {
+    board.king(Color::White)?;
+    board.king(Color::Black)?;
+    let white = pieces(board, Color::White);
+    let black = pieces(board, Color::Black);
+    if white.is_empty() && black.is_empty() {
+        return Some(0.0);
+    }
+    let (strong, pieces) = match (&white[..], &black[..]) {
+        (pieces, []) => (Color::White, pieces),
+        ([], pieces) => (Color::Black, pieces),
+        _ => return None,
+    };
+    let score = if let Some(win) = kpk(board, turn) {
+        match win {
+            true => KNOWN_WIN + pawn_advance(board, strong),
+            false => 0.0,
+        }
+    } else if pieces.contains(&Kind::Pawn) {
+        wrong_bishop(board, strong, pieces).then_some(0.0)?
+    } else if pieces.len() == 2 && pieces.contains(&Kind::Bishop) && pieces.contains(&Kind::Knight)
+    {
+        KNOWN_WIN + value(pieces) + bishop_corner(board, strong) + kings_close(board)
+    } else if pieces.contains(&Kind::Queen) || pieces.contains(&Kind::Rook) {
+        KNOWN_WIN + value(pieces) + push_to_edge(board, strong) + kings_close(board)
+    } else {
+        return None;
+    };
+    Some(if turn == strong { score } else { -score })
+}
+
+/// Whether `color` has the material to win: pawns, or at least a rook or
+/// two minor pieces.
+pub fn can_win(board: &Board, color: Color) -> bool {
+    board
+        .colored_pieces(color)
+        .any(|(piece, _)| piece.kind == Kind::Pawn)
+        || material(board, color) > 1.0
+}
+
+/// The pieces of a side other than its king.
+fn pieces(board: &Board, color: Color) -> Vec<Kind> {
+    board
+        .colored_pieces(color)
+        .map(|(piece, _)| piece.kind)
+        .filter(|&kind| kind != Kind::King)
+        .collect()
+}
+
+fn value(pieces: &[Kind]) -> f32 {
+    pieces
+        .iter()
+        .map(|kind| match kind {
+            Kind::Queen => 9.0,
+            Kind::Rook => 5.0,
+            Kind::Bishop | Kind::Knight => 3.0,
+            Kind::Pawn => 1.0,
+            Kind::King => 0.0,
+        })
+        .sum()
+}
+
+fn king(board: &Board, color: Color) -> Position {
+    board
+        .king(color)
+        .expect("both kings are on the board")
+}
+
+fn distance(a: Position, b: Position) -> isize {
+    (a.rank - b.rank)
+        .abs()
+        .max((a.file - b.file).abs())
+}
+
+/// Rewards driving the lone king away from the center.
+fn push_to_edge(board: &Board, strong: Color) -> f32 {
+    let king = king(board, strong.opposite());
+    let center = |x: isize| (3 - x).max(x - 4);
+    0.2 * (center(king.rank) + center(king.file)) as f32
+}
+
+/// Rewards bringing the kings together.
+fn kings_close(board: &Board) -> f32 {
+    0.1 * (7 - distance(king(board, Color::White), king(board, Color::Black))) as f32
+}
+
+/// Rewards driving the lone king to a corner the bishop controls, the
+/// only ones where the mate can be given.
+fn bishop_corner(board: &Board, strong: Color) -> f32 {
+    let Some((_, bishop)) = board
+        .colored_pieces(strong)
+        .find(|(piece, _)| piece.kind == Kind::Bishop)
+    else {
+        return 0.0;
+    };
+    let corners = match light_square(bishop) {
+        true => [(0, 0), (7, 7)],
+        false => [(0, 7), (7, 0)],
+    };
+    let king = king(board, strong.opposite());
+    let corner = corners
+        .into_iter()
+        .map(|corner| distance(king, Position::from(corner)))
+        .min()
+        .unwrap_or_default();
+    0.3 * (7 - corner) as f32
+}
+
+fn light_square(pos: Position) -> bool {
+    (pos.rank + pos.file) % 2 == 0
+}
+
+/// Rewards the pawn for each rank it has advanced.
+fn pawn_advance(board: &Board, strong: Color) -> f32 {
+    board
+        .colored_pieces(strong)
+        .filter(|(piece, _)| piece.kind == Kind::Pawn)
+        .map(|(_, pos)| 0.1 * (pos.rank - strong.pawn_start()).abs() as f32)
+        .sum()
+}
+
+/// A bishop and pawns on a single rook file cannot win when the bishop
+/// does not control the promotion square and the defending king reaches
+/// it.
+fn wrong_bishop(board: &Board, strong: Color, pieces: &[Kind]) -> bool {
+    let mut bishops = board
+        .colored_pieces(strong)
+        .filter(|(piece, _)| piece.kind == Kind::Bishop);
+    let (Some((_, bishop)), None) = (bishops.next(), bishops.next()) else {
+        return false;
+    };
+    if pieces
+        .iter()
+        .any(|&kind| kind != Kind::Pawn && kind != Kind::Bishop)
+    {
+        return false;
+    }
+    let mut files = board
+        .colored_pieces(strong)
+        .filter(|(piece, _)| piece.kind == Kind::Pawn)
+        .map(|(_, pos)| pos.file);
+    let Some(file) = files.next() else {
+        return false;
+    };
+    if (file != 0 && file != 7) || files.any(|other| other != file) {
+        return false;
+    }
+    let promotion = Position {
+        rank: strong.opposite().back_rank(),
+        file,
+    };
+    light_square(promotion) != light_square(bishop)
+        && distance(king(board, strong.opposite()), promotion) <= 1
+}
+
+/// Whether the side with the pawn wins with king and pawn against king,
+/// `None` for any other material, a missing king or a pawn on the first
+/// or last rank.
+pub fn kpk(board: &Board, turn: Color) -> Option<bool> {
+    let mut pawns = vec![];
+    let mut others = 0;
+    for color in [Color::White, Color::Black] {
+        for (piece, pos) in board.colored_pieces(color) {
+            match piece.kind {
+                Kind::Pawn => pawns.push((piece.color, pos)),
+                Kind::King => (),
+                _ => others += 1,
+            }
+        }
+    }
+    let (&[(strong, pawn)], 0) = (&pawns[..], others) else {
+        return None;
+    };
+    if pawn.rank == 0 || pawn.rank == 7 {
+        return None;
+    }
+    // the bitbase has white pawns on the a to d files
+    let flip = pawn.file > 3;
+    let square = |pos: Position| {
+        let rank = match strong {
+            Color::White => 7 - pos.rank,
+            Color::Black => pos.rank,
+        };
+        let file = if flip { 7 - pos.file } else { pos.file };
+        (rank * 8 + file) as usize
+    };
+    let strong_king = square(board.king(strong)?);
+    let weak_king = square(board.king(strong.opposite())?);
+    Some(KPK.win(turn == strong, strong_king, weak_king, square(pawn)))
+}
+
+/// Every position of king and pawn against king with the pawn on the a
+/// to d files, with a bit set if white wins. Squares are numbered from
+/// a1 to h8 here.
+struct Bitbase {
+    wins: Vec<u64>,
+}
+
+const KPK_SIZE: usize = 2 * 64 * 64 * 24;
+
+static KPK: Lazy<Bitbase> = Lazy::new(Bitbase::kpk);
+
+const INVALID: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+const UNKNOWN: u8 = 8;
+
+fn index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn = (pawn / 8 - 1) * 4 + pawn % 8;
+    ((pawn * 64 + black_king) * 64 + white_king) * 2 + white_to_move as usize
+}
+
+fn king_moves(square: usize) -> impl Iterator<Item = usize> + Clone {
+    let (rank, file) = ((square / 8) as isize, (square % 8) as isize);
+    (-1..=1)
+        .flat_map(|x| (-1..=1).map(move |y| (x, y)))
+        .filter(|&step| step != (0, 0))
+        .map(move |(x, y)| (rank + x, file + y))
+        .filter(|&(rank, file)| (0..8).contains(&rank) && (0..8).contains(&file))
+        .map(|(rank, file)| (rank * 8 + file) as usize)
+}
+
+fn square_distance(a: usize, b: usize) -> usize {
+    (a / 8)
+        .abs_diff(b / 8)
+        .max((a % 8).abs_diff(b % 8))
+}
+
+fn pawn_attacks(pawn: usize, square: usize) -> bool {
+    square / 8 == pawn / 8 + 1 && (square % 8).abs_diff(pawn % 8) == 1
+}
+
+impl Bitbase {
+    fn win(&self, white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> bool {
+        let index = index(white_to_move, white_king, black_king, pawn);
+        self.wins[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Solves the ending by retrograde analysis: the positions decided by
+    /// the rules are classified first, and the rest are resolved from
+    /// their successors until nothing changes. What is left is drawn.
+    fn kpk() -> Bitbase {
+        let mut results = vec![UNKNOWN; KPK_SIZE];
+        let positions = || {
+            (8..56)
+                .filter(|pawn| pawn % 8 < 4)
+                .flat_map(|pawn| (0..64).map(move |black_king| (black_king, pawn)))
+                .flat_map(|(black_king, pawn)| {
+                    (0..64).flat_map(move |white_king| {
+                        [true, false]
+                            .map(|white_to_move| (white_to_move, white_king, black_king, pawn))
+                    })
+                })
+        };
+        for (white_to_move, white_king, black_king, pawn) in positions() {
+            results[index(white_to_move, white_king, black_king, pawn)] =
+                classify(white_to_move, white_king, black_king, pawn);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (white_to_move, white_king, black_king, pawn) in positions() {
+                let index = index(white_to_move, white_king, black_king, pawn);
+                if results[index] != UNKNOWN {
+                    continue;
+                }
+                let successors = successors(white_to_move, white_king, black_king, pawn)
+                    .into_iter()
+                    .fold(0, |all, successor| all | results[successor]);
+                // white wins if any move wins, black draws if any move draws
+                let (good, bad) = match white_to_move {
+                    true => (WIN, DRAW),
+                    false => (DRAW, WIN),
+                };
+                if successors & good != 0 {
+                    results[index] = good;
+                } else if successors == bad {
+                    results[index] = bad;
+                }
+                changed |= results[index] != UNKNOWN;
+            }
+        }
+        let mut wins = vec![0; KPK_SIZE / 64];
+        for (index, &result) in results.iter().enumerate() {
+            if result == WIN {
+                wins[index / 64] |= 1 << (index % 64);
+            }
+        }
+        Bitbase { wins }
+    }
+}
+
+/// The result of positions decided without looking at the moves after
+/// them, if it is known yet.
+fn classify(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> u8 {
+    if white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || square_distance(white_king, black_king) <= 1
+        || (white_to_move && pawn_attacks(pawn, black_king))
+    {
+        return INVALID;
+    }
+    if white_to_move {
+        // the pawn promotes and the queen cannot be taken
+        let queen = pawn + 8;
+        let promotes = pawn / 8 == 6
+            && white_king != queen
+            && black_king != queen
+            && (square_distance(black_king, queen) > 1 || square_distance(white_king, queen) == 1);
+        return if promotes { WIN } else { UNKNOWN };
+    }
+    let mut moves = king_moves(black_king)
+        .filter(|&square| square_distance(square, white_king) > 1 && !pawn_attacks(pawn, square));
+    match moves.clone().next() {
+        None if pawn_attacks(pawn, black_king) => WIN,
+        None => DRAW,
+        // the pawn is taken
+        Some(_) if moves.any(|square| square == pawn) => DRAW,
+        Some(_) => UNKNOWN,
+    }
+}
+
+fn successors(
+    white_to_move: bool,
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+) -> Vec<usize> {
+    if !white_to_move {
+        return king_moves(black_king)
+            .filter(|&square| {
+                square_distance(square, white_king) > 1 && !pawn_attacks(pawn, square)
+            })
+            .map(|square| index(true, white_king, square, pawn))
+            .collect();
+    }
+    let mut successors: Vec<_> = king_moves(white_king)
+        .filter(|&square| square != pawn && square_distance(square, black_king) > 1)
+        .map(|square| index(false, square, black_king, pawn))
+        .collect();
+    // promotions that do not win right away are left to the king moves
+    let free = |square| square != white_king && square != black_king;
+    if pawn / 8 < 6 && free(pawn + 8) {
+        successors.push(index(false, white_king, black_king, pawn + 8));
+        if pawn / 8 == 1 && free(pawn + 16) {
+            successors.push(index(false, white_king, black_king, pawn + 16));
+        }
+    }
+    successors
+}
+
+#[test]
+fn king_and_pawn() {
+    let kpk = |fen| {
+        let (board, turn) = Board::from_fen(fen).unwrap();
+        kpk(&board, turn)
+    };
+    // the king on the sixth rank in front of its pawn wins
+    assert_eq!(kpk("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some(true));
+    assert_eq!(kpk("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some(true));
+    // but not with a rook pawn
+    assert_eq!(kpk("k7/8/K7/P7/8/8/8/8 w - - 0 1"), Some(false));
+    // the king is outside the square of the pawn
+    assert_eq!(kpk("8/8/8/8/7P/8/8/k6K b - - 0 1"), Some(true));
+    assert_eq!(kpk("8/8/8/8/3k3P/8/8/7K b - - 0 1"), Some(false));
+    assert_eq!(kpk("k7/8/8/p7/8/8/8/7K b - - 0 1"), Some(true));
+    // the opposition decides
+    assert_eq!(kpk("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1"), Some(false));
+    assert_eq!(kpk("8/8/8/4k3/8/4K3/4P3/8 b - - 0 1"), Some(true));
+    assert_eq!(kpk("8/8/8/4k3/8/4KN2/4P3/8 w - - 0 1"), None);
+}
+
+#[test]
+fn known_endings() {
+    let evaluate = |fen| {
+        let (board, turn) = Board::from_fen(fen).unwrap();
+        evaluate(&board, turn)
+    };
+    let corner = evaluate("k7/8/8/8/8/8/8/KQ6 w - - 0 1").unwrap();
+    let center = evaluate("8/8/8/3k4/8/8/8/KQ6 w - - 0 1").unwrap();
+    assert!(KNOWN_WIN < center && center < corner);
+    assert!(evaluate("8/8/8/3k4/8/8/8/KR6 b - - 0 1").unwrap() < -KNOWN_WIN);
+    // the dark squared bishop mates on a1 and h8
+    let right = evaluate("7k/8/8/8/8/8/8/KNB5 w - - 0 1").unwrap();
+    let wrong = evaluate("k7/8/8/8/8/8/8/KNB5 w - - 0 1").unwrap();
+    assert!(KNOWN_WIN < wrong && wrong < right);
+    // a8 is a light square
+    assert_eq!(evaluate("k7/8/8/8/8/8/PB6/K7 w - - 0 1"), Some(0.0));
+    assert_eq!(evaluate("k7/8/8/8/8/8/P1B5/K7 w - - 0 1"), None);
+    assert_eq!(evaluate("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(0.0));
+}
+
+#[test]
+fn impossible_positions() {
+    use crate::minimax::Limits;
+    use crate::settings::EvalConfig;
+    // pawns on the first or last rank, and a missing king, are left to the
+    // evaluation and searched without panicking
+    for fen in [
+        "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+        "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "8/8/8/8/8/8/8/KR6 w - - 0 1",
+    ] {
+        let (board, turn) = Board::from_fen(fen).unwrap();
+        assert_eq!(kpk(&board, turn), None);
+        assert_eq!(evaluate(&board, turn), None);
+        let limits = Limits {
+            depth: 2,
+            ..Default::default()
+        };
+        let result = crate::search::search(board, turn, limits, &EvalConfig::default());
+        assert!(result.best_move.is_some());
+    }
+}
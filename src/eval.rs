@@ -0,0 +1,567 @@
+use crate::endgame::{self, can_win};
+use crate::moves::{Play, Position};
+use crate::pawns::{pawn_structure, side_structure, PawnHash};
+use crate::piece::{Color, Kind, Piece};
+use crate::settings::EvalConfig;
+use crate::table::{Board, CastleSide};
+
+use std::fmt::Display;
+
+/// The static evaluation of a position from the point of view of `turn`.
+pub fn evaluate(board: &Board, turn: Color, config: &EvalConfig) -> f32 {
+    evaluate_with(board, turn, config, None)
+}
+
+/// Like [`evaluate`], looking up the pawn structure in `pawns` when a
+/// pawn hash table is given. Known endings are scored by
+/// [`endgame::evaluate`] instead, and a side without the material to
+/// win is never scored ahead.
+pub fn evaluate_with(
+    board: &Board,
+    turn: Color,
+    config: &EvalConfig,
+    pawns: Option<&mut PawnHash>,
+) -> f32 {
+    if let Some(score) = endgame::evaluate(board, turn) {
+        return score;
+    }
+    let phase = phase(board);
+    let player_score: f32 = board
+        .colored_pieces(turn)
+        .map(|piece| config.value(piece, phase))
+        .sum();
+    let opponent_score: f32 = board
+        .colored_pieces(turn.opposite())
+        .map(|piece| config.value(piece, phase))
+        .sum();
+    let pawns = match turn {
+        Color::White => pawn_structure(board, config, pawns),
+        Color::Black => -pawn_structure(board, config, pawns),
+    };
+    let score = player_score - opponent_score
+        + pawns
+        + castling(board, turn, config)
+        + king_safety(board, turn, config)
+        + activity(board, turn, config)
+        - castling(board, turn.opposite(), config)
+        - king_safety(board, turn.opposite(), config)
+        - activity(board, turn.opposite(), config);
+    clamp_draws(board, turn, score)
+}
+
+/// Keeps a side that cannot win from being scored as better.
+fn clamp_draws(board: &Board, turn: Color, score: f32) -> f32 {
+    match (can_win(board, turn), can_win(board, turn.opposite())) {
+        (false, false) => 0.0,
+        (false, true) => score.min(0.0),
+        (true, false) => score.max(0.0),
+        (true, true) => score,
+    }
+}
+
+/// The phase of the game computed from the remaining material, from 1
+/// with all the pieces on the board to 0 when only kings and pawns remain.
+pub fn phase(board: &Board) -> f32 {
+    const TOTAL: f32 = 24.0;
+    let material = material(board, Color::White) + material(board, Color::Black);
+    material.min(TOTAL) / TOTAL
+}
+
+/// The non-pawn material of a side, with minor pieces worth 1, rooks 2
+/// and queens 4.
+pub fn material(board: &Board, color: Color) -> f32 {
+    board
+        .colored_pieces(color)
+        .map(|(piece, _)| match piece.kind {
+            Kind::Knight | Kind::Bishop => 1.0,
+            Kind::Rook => 2.0,
+            Kind::Queen => 4.0,
+            Kind::King | Kind::Pawn => 0.0,
+        })
+        .sum()
+}
+
+/// Scores the pawn shield and storm, the open files around the king of
+/// `color`, and the squares next to it attacked by enemy pieces. Nothing
+/// is counted if the opponent lacks the material to mount an attack.
+pub fn king_safety(board: &Board, color: Color, config: &EvalConfig) -> f32 {
+    let weights = &config.king_safety;
+    let enemy = color.opposite();
+    let Some(king) = board.king(color) else {
+        return 0.0;
+    };
+    if material(board, enemy) < weights.min_material {
+        return 0.0;
+    }
+    let dir = color.pawn_dir();
+    let pawn = |pos: Position| {
+        board
+            .get(pos)
+            .filter(|piece| piece.kind == Kind::Pawn)
+            .map(|piece| piece.color)
+    };
+    let mut score = 0.0;
+    for file in (king.file - 1..=king.file + 1).filter(|file| (0..8).contains(file)) {
+        let pawns: Vec<_> = (0..8)
+            .filter_map(|rank| Some((rank, pawn(Position { rank, file })?)))
+            .collect();
+        let own = pawns
+            .iter()
+            .any(|&(_, pawn)| pawn == color);
+        if pawns.is_empty() {
+            score += weights.open_file;
+        } else if !own {
+            score += weights.half_open_file;
+        }
+        for &(rank, pawn) in &pawns {
+            // how many ranks in front of the king the pawn stands
+            let distance = (rank - king.rank) * dir;
+            if pawn == color && distance == 1 {
+                score += weights.shield;
+            } else if pawn == color && distance == 2 {
+                score += weights.shield_far;
+            } else if pawn == enemy && (1..4).contains(&distance) {
+                score += weights.storm;
+            }
+        }
+    }
+    let zone =
+        |pos: Position| (pos.rank - king.rank).abs() <= 1 && (pos.file - king.file).abs() <= 1;
+    let attacks = &weights.attacks;
+    let danger: f32 = board
+        .colored_pieces(enemy)
+        .filter_map(|(piece, pos)| {
+            let weight = match piece.kind {
+                Kind::Knight => attacks.knight,
+                Kind::Bishop => attacks.bishop,
+                Kind::Rook => attacks.rook,
+                Kind::Queen => attacks.queen,
+                Kind::King | Kind::Pawn => return None,
+            };
+            let count = board
+                .moves_for(pos)
+                .filter(|play| zone(play.r#move().to))
+                .count();
+            Some(weight * count as f32)
+        })
+        .sum();
+    score - danger
+}
+
+/// Rewards a king that has castled, and penalizes each castling right
+/// lost without castling, so the king does not wander in the opening.
+pub fn castling(board: &Board, color: Color, config: &EvalConfig) -> f32 {
+    let castle = board.castle(color);
+    match castle.castled {
+        Some(CastleSide::Kingside) => config.kingside_castle,
+        Some(CastleSide::Queenside) => config.queenside_castle,
+        None => {
+            let mut score = 0.0;
+            if !castle.kingside {
+                score -= config.kingside_castle;
+            }
+            if !castle.queenside {
+                score -= config.queenside_castle;
+            }
+            score
+        }
+    }
+}
+
+/// Scores the mobility of the pieces of `color`, rooks on open files,
+/// the bishop pair, knight outposts, and the pieces it defends and attacks.
+pub fn activity(board: &Board, color: Color, config: &EvalConfig) -> f32 {
+    let (mobility, activity) = activity_terms(board, color, config);
+    mobility + activity
+}
+
+/// The mobility of the pieces of `color`, and the rest of its activity terms.
+fn activity_terms(board: &Board, color: Color, config: &EvalConfig) -> (f32, f32) {
+    let weights = &config.activity;
+    let enemy = color.opposite();
+    let dir = color.pawn_dir();
+    let pawn = |rank: isize, file: isize, color: Color| {
+        board.get(Position { rank, file })
+            == Some(Piece {
+                kind: Kind::Pawn,
+                color,
+            })
+    };
+    // a square is attacked by a pawn standing diagonally in front of it
+    let pawn_attacked = |pos: Position| {
+        pawn(pos.rank - enemy.pawn_dir(), pos.file - 1, enemy)
+            || pawn(pos.rank - enemy.pawn_dir(), pos.file + 1, enemy)
+    };
+    let bit = |pos: Position| 1u64 << (pos.rank * 8 + pos.file);
+    let mut defended = 0;
+    let mut attacked = 0;
+    let mut bishops = 0;
+    let mut mobility_score = 0.0;
+    let mut score = 0.0;
+    for (piece, pos) in board.colored_pieces(color) {
+        let mut safe = 0;
+        for play in board.moves_for(pos) {
+            match play {
+                Play::Defense(r#move, target) if target.kind != Kind::King => {
+                    defended |= bit(r#move.to);
+                }
+                Play::Capture(r#move, target) => {
+                    if target.kind != Kind::King {
+                        attacked |= bit(r#move.to);
+                    }
+                    safe += !pawn_attacked(r#move.to) as u32;
+                }
+                Play::Move(r#move) => safe += !pawn_attacked(r#move.to) as u32,
+                _ => (),
+            }
+        }
+        let mobility = match piece.kind {
+            Kind::Knight => weights.mobility.knight,
+            Kind::Bishop => weights.mobility.bishop,
+            Kind::Rook => weights.mobility.rook,
+            Kind::Queen => weights.mobility.queen,
+            Kind::King | Kind::Pawn => 0.0,
+        };
+        mobility_score += mobility * safe as f32;
+        match piece.kind {
+            Kind::Rook => {
+                let own = (0..8).any(|rank| pawn(rank, pos.file, color));
+                let theirs = (0..8).any(|rank| pawn(rank, pos.file, enemy));
+                if !own && !theirs {
+                    score += weights.rook_open_file;
+                } else if !own {
+                    score += weights.rook_half_open_file;
+                }
+            }
+            Kind::Bishop => bishops += 1,
+            Kind::Knight => {
+                let advanced = (pos.rank - color.back_rank()).abs();
+                let supported = pawn(pos.rank - dir, pos.file - 1, color)
+                    || pawn(pos.rank - dir, pos.file + 1, color);
+                // an enemy pawn on an adjacent file in front of the
+                // knight can still advance to attack it
+                let chased = (0..8)
+                    .filter(|rank| (rank - pos.rank) * dir > 0)
+                    .any(|rank| pawn(rank, pos.file - 1, enemy) || pawn(rank, pos.file + 1, enemy));
+                if advanced >= 4 && supported && !chased {
+                    score += weights.knight_outpost;
+                }
+            }
+            _ => (),
+        }
+    }
+    if bishops >= 2 {
+        score += weights.bishop_pair;
+    }
+    score += weights.defended * defended.count_ones() as f32
+        + weights.attacked * attacked.count_ones() as f32;
+    (mobility_score, score)
+}
+
+/// The evaluation terms of one side, in pawns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Terms {
+    /// The average value of each piece over the squares it can stand on.
+    pub material: f32,
+    /// How much better, or worse, than average the squares of the pieces
+    /// of each kind are, indexed like [`KINDS`].
+    pub placement: [f32; 6],
+    pub mobility: f32,
+    /// Rooks on open files, the bishop pair, outposts, and the pieces
+    /// defended and attacked.
+    pub activity: f32,
+    pub pawns: f32,
+    pub king_safety: f32,
+    pub castling: f32,
+}
+
+impl Terms {
+    pub fn total(&self) -> f32 {
+        self.material
+            + self
+                .placement
+                .iter()
+                .sum::<f32>()
+            + self.mobility
+            + self.activity
+            + self.pawns
+            + self.king_safety
+            + self.castling
+    }
+}
+
+pub const KINDS: [Kind; 6] = [
+    Kind::Pawn,
+    Kind::Knight,
+    Kind::Bishop,
+    Kind::Rook,
+    Kind::Queen,
+    Kind::King,
+];
+
+/// The evaluation of a position split into its terms for each side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub phase: f32,
+    pub white: Terms,
+    pub black: Terms,
+    /// What the known endings, and the clamp for sides without the
+    /// material to win, add to the terms, from the point of view of white.
+    pub endgame: f32,
+}
+
+impl Explanation {
+    /// The evaluation from the point of view of white.
+    pub fn total(&self) -> f32 {
+        self.white.total() - self.black.total() + self.endgame
+    }
+}
+
+/// Evaluates the position term by term, so it can be seen why the
+/// engine likes it. The totals add up to [`evaluate`].
+pub fn explain(board: &Board, config: &EvalConfig) -> Explanation {
+    let phase = phase(board);
+    let terms = |color| {
+        let mut terms = Terms::default();
+        for (piece, pos) in board.colored_pieces(color) {
+            let material = average_value(piece.kind, phase, config);
+            let index = KINDS
+                .iter()
+                .position(|&kind| kind == piece.kind)
+                .unwrap_or_default();
+            terms.material += material;
+            terms.placement[index] += config.value((piece, pos), phase) - material;
+        }
+        (terms.mobility, terms.activity) = activity_terms(board, color, config);
+        terms.pawns = side_structure(board, color, config);
+        terms.king_safety = king_safety(board, color, config);
+        terms.castling = castling(board, color, config);
+        terms
+    };
+    let (white, black) = (terms(Color::White), terms(Color::Black));
+    let terms = white.total() - black.total();
+    let score = endgame::evaluate(board, Color::White)
+        .unwrap_or_else(|| clamp_draws(board, Color::White, terms));
+    Explanation {
+        phase,
+        endgame: score - terms,
+        white,
+        black,
+    }
+}
+
+/// The mean of the tapered table of a piece over the squares it can
+/// stand on, which for pawns excludes the first and last ranks.
+fn average_value(kind: Kind, phase: f32, config: &EvalConfig) -> f32 {
+    let ranks = match kind {
+        Kind::Pawn => 1..7,
+        _ => 0..8,
+    };
+    let squares = ranks.len() * 8;
+    let tables = config.table(kind);
+    let sum: f32 = ranks
+        .flat_map(|rank| (0..8).map(move |file| (rank, file)))
+        .map(|(rank, file)| {
+            tables.midgame.white[rank][file] * phase
+                + tables.endgame.white[rank][file] * (1.0 - phase)
+        })
+        .sum();
+    sum / squares as f32
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let row = |f: &mut std::fmt::Formatter<'_>, name: &str, white: f32, black: f32| {
+            writeln!(
+                f,
+                "{name:<18}{white:>10.2}{black:>10.2}{:>+10.2}",
+                white - black
+            )
+        };
+        writeln!(
+            f,
+            "{:<18}{:>10}{:>10}{:>10}",
+            "Term", "White", "Black", "Total"
+        )?;
+        row(f, "Material", self.white.material, self.black.material)?;
+        for (i, kind) in KINDS.iter().enumerate() {
+            let name = format!("  {kind:?} squares");
+            row(f, &name, self.white.placement[i], self.black.placement[i])?;
+        }
+        row(f, "Mobility", self.white.mobility, self.black.mobility)?;
+        row(f, "Activity", self.white.activity, self.black.activity)?;
+        row(f, "Pawn structure", self.white.pawns, self.black.pawns)?;
+        row(
+            f,
+            "King safety",
+            self.white.king_safety,
+            self.black.king_safety,
+        )?;
+        row(f, "Castling", self.white.castling, self.black.castling)?;
+        // known endings and draws are scored for the position as a whole
+        writeln!(f, "{:<38}{:>+10.2}", "Endgame", self.endgame)?;
+        writeln!(
+            f,
+            "{:<18}{:>10.2}{:>10.2}{:>+10.2}",
+            "Total",
+            self.white.total(),
+            self.black.total(),
+            self.total()
+        )?;
+        write!(f, "Game phase: {:.2}", self.phase)
+    }
+}
+
+#[test]
+fn castling_weights() {
+    let config = EvalConfig::default();
+    let (board, turn) = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(castling(&board, turn, &config), 0.0);
+
+    let mut castled = board;
+    castled.apply(
+        castled
+            .parse_san("O-O", turn)
+            .unwrap(),
+    );
+    assert_eq!(castling(&castled, turn, &config), config.kingside_castle);
+
+    let mut walked = board;
+    walked.apply(
+        walked
+            .parse_san("Kf1", turn)
+            .unwrap(),
+    );
+    assert_eq!(
+        castling(&walked, turn, &config),
+        -config.kingside_castle - config.queenside_castle
+    );
+    assert!(evaluate(&castled, turn, &config) > evaluate(&walked, turn, &config));
+}
+
+#[test]
+fn symmetric_evaluation() {
+    let config = EvalConfig::default();
+    let positions = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "6k1/5ppp/8/8/3P4/8/5PPP/R5K1 w - - 0 1",
+    ];
+    for fen in positions {
+        let (board, _) = Board::from_fen(fen).unwrap();
+        let mirror = board.mirror();
+        // from white's point of view the mirror has the opposite score
+        let score = evaluate(&board, Color::White, &config);
+        let mirrored = evaluate(&mirror, Color::White, &config);
+        assert!(
+            (score + mirrored).abs() < 1e-3,
+            "{fen}: {score} != -{mirrored}"
+        );
+        assert_eq!(mirror.mirror(), board);
+    }
+}
+
+#[test]
+fn sheltered_king() {
+    let config = EvalConfig::default();
+    let sheltered = "r2q1rk1/pppb1ppp/2n2n2/8/8/2N2N2/PPPB1PPP/R2Q1RK1 w - - 0 1";
+    let exposed = "r2q1rk1/pppb1ppp/2n2n2/8/8/2N2N2/PPPB4/R2Q1RK1 w - - 0 1";
+    let (sheltered, turn) = Board::from_fen(sheltered).unwrap();
+    let (exposed, _) = Board::from_fen(exposed).unwrap();
+    assert_eq!(
+        king_safety(&sheltered, turn, &config),
+        3.0 * config.king_safety.shield
+    );
+    assert!(king_safety(&exposed, turn, &config) < 0.0);
+
+    // without enough material to attack the king nothing is counted
+    let (endgame, turn) = Board::from_fen("6k1/5ppp/8/8/8/8/8/1R4K1 w - - 0 1").unwrap();
+    assert_eq!(king_safety(&endgame, turn, &config), 0.0);
+}
+
+#[test]
+fn piece_activity() {
+    let mut config = EvalConfig::default();
+    let activity_of = |fen: &str, config: &EvalConfig| {
+        let (board, turn) = Board::from_fen(fen).unwrap();
+        activity(&board, turn, config)
+    };
+    // a knight in the corner has two safe squares, one when a pawn covers b3
+    let mobility = config
+        .activity
+        .mobility
+        .knight;
+    assert_eq!(
+        activity_of("4k3/8/8/8/8/8/8/N3K3 w - - 0 1", &config),
+        2.0 * mobility
+    );
+    assert_eq!(
+        activity_of("4k3/8/8/8/p7/8/8/N3K3 w - - 0 1", &config),
+        mobility
+    );
+
+    // leave only the structural terms
+    config
+        .activity
+        .mobility
+        .knight = 0.0;
+    config
+        .activity
+        .mobility
+        .bishop = 0.0;
+    config.activity.mobility.rook = 0.0;
+    config.activity.mobility.queen = 0.0;
+    config.activity.defended = 0.0;
+    config.activity.attacked = 0.0;
+    let weights = config.activity.clone();
+    // d5 is defended by e4, and only c7 could chase the knight away
+    let outpost = activity_of("4k3/p6p/8/3N4/4P3/8/8/4K3 w - - 0 1", &config);
+    let chased = activity_of("4k3/p1p4p/8/3N4/4P3/8/8/4K3 w - - 0 1", &config);
+    assert_eq!(outpost - chased, weights.knight_outpost);
+    let pair = activity_of("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", &config);
+    let single = activity_of("4k3/8/8/8/8/8/8/2N1KB2 w - - 0 1", &config);
+    assert_eq!(pair - single, weights.bishop_pair);
+    assert_eq!(
+        activity_of("4k3/p7/8/8/8/8/8/R3K2R w - - 0 1", &config),
+        weights.rook_half_open_file + weights.rook_open_file
+    );
+}
+
+#[test]
+fn explanation_adds_up() {
+    let config = EvalConfig::default();
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let (board, _) = Board::from_fen(fen).unwrap();
+    let explanation = explain(&board, &config);
+    let score = evaluate(&board, Color::White, &config);
+    assert!((explanation.total() - score).abs() < 1e-2);
+    assert_eq!(explanation.endgame, 0.0);
+    assert_eq!(
+        explanation
+            .to_string()
+            .lines()
+            .count(),
+        16
+    );
+
+    // known wins and sides that cannot win replace the terms
+    for fen in [
+        "8/8/8/3k4/8/8/8/KR6 w - - 0 1",
+        "8/8/8/3k4/8/8/8/KN6 w - - 0 1",
+    ] {
+        let (board, _) = Board::from_fen(fen).unwrap();
+        let explanation = explain(&board, &config);
+        let score = evaluate(&board, Color::White, &config);
+        assert!(explanation.endgame != 0.0);
+        assert!((explanation.total() - score).abs() < 1e-2);
+    }
+}
+
+#[test]
+fn game_phase() {
+    assert_eq!(phase(&Board::default()), 1.0);
+    let (board, _) = Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").unwrap();
+    assert_eq!(phase(&board), 0.0);
+}
@@ -0,0 +1,195 @@
+use std::fmt::Display;
+
+use crate::moves::Position;
+use crate::piece::{Color, Kind, Piece};
+use crate::table::{Board, Castle, CastleSide};
+use tap::Pipe;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// The FEN string has fewer than the two required fields.
+    MissingField(&'static str),
+    /// The piece placement does not describe an 8x8 board.
+    BadPlacement(String),
+    BadTurn(String),
+    BadCastling(String),
+    BadEnPassant(String),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing FEN field: {field}"),
+            FenError::BadPlacement(s) => write!(f, "invalid piece placement: {s:?}"),
+            FenError::BadTurn(s) => write!(f, "invalid side to move: {s:?}"),
+            FenError::BadCastling(s) => write!(f, "invalid castling rights: {s:?}"),
+            FenError::BadEnPassant(s) => write!(f, "invalid en passant square: {s:?}"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Board {
+    /// Returns the FEN of the board with `turn` to move. Since the board
+    /// does not keep track of clocks, the halfmove clock and fullmove number
+    /// are always `0 1`.
+    pub fn fen(&self, turn: Color) -> String {
+        let mut fen = String::new();
+        for (r, row) in self.table.iter().enumerate() {
+            let mut empty = 0;
+            for square in row {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.symbol());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if r != 7 {
+                fen.push('/');
+            }
+        }
+        fen.push(' ');
+        fen.push(match turn {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+        fen.push(' ');
+        let castling: String = [
+            (self.white_castle.kingside, 'K'),
+            (self.white_castle.queenside, 'Q'),
+            (self.black_castle.kingside, 'k'),
+            (self.black_castle.queenside, 'q'),
+        ]
+        .into_iter()
+        .filter_map(|(right, symbol)| right.then_some(symbol))
+        .collect();
+        if castling.is_empty() {
+            fen.push('-');
+        } else {
+            fen.push_str(&castling);
+        }
+        match self.en_passant {
+            Some(pos) => fen.push_str(&format!(" {pos} 0 1")),
+            None => fen.push_str(" - 0 1"),
+        }
+        fen
+    }
+
+    /// Parses a FEN string, returning the board and the side to move.
+    /// Only the placement and side to move fields are required.
+    pub fn from_fen(fen: &str) -> Result<(Board, Color), FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields
+            .next()
+            .ok_or(FenError::MissingField("piece placement"))?;
+        let turn = fields
+            .next()
+            .ok_or(FenError::MissingField("side to move"))?;
+        let castling = fields.next().unwrap_or("-");
+        let en_passant = fields.next().unwrap_or("-");
+
+        let bad_placement = || FenError::BadPlacement(placement.to_string());
+        let mut table = [[None; 8]; 8];
+        let rows: Vec<_> = placement.split('/').collect();
+        if rows.len() != 8 {
+            return Err(bad_placement());
+        }
+        for (rank, row) in rows.into_iter().enumerate() {
+            let mut file = 0;
+            for symbol in row.chars() {
+                if let Some(skip) = symbol.to_digit(10) {
+                    file += skip as usize;
+                } else {
+                    let piece = Piece::from_symbol(symbol).ok_or_else(bad_placement)?;
+                    *table[rank]
+                        .get_mut(file)
+                        .ok_or_else(bad_placement)? = Some(piece);
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err(bad_placement());
+            }
+        }
+
+        let turn = match turn {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::BadTurn(turn.to_string())),
+        };
+
+        if castling != "-"
+            && !castling
+                .chars()
+                .all(|c| "KQkq".contains(c))
+        {
+            return Err(FenError::BadCastling(castling.to_string()));
+        }
+        let en_passant = match en_passant {
+            "-" => None,
+            square => square
+                .parse::<Position>()
+                .map_err(|_| FenError::BadEnPassant(square.to_string()))?
+                .pipe(Some),
+        };
+        let castle = |kingside, queenside, color| Castle {
+            kingside,
+            queenside,
+            castled: match kingside || queenside {
+                true => None,
+                false => castled(&table, color),
+            },
+        };
+        let board = Board {
+            table,
+            en_passant,
+            white_castle: castle(castling.contains('K'), castling.contains('Q'), Color::White),
+            black_castle: castle(castling.contains('k'), castling.contains('q'), Color::Black),
+        };
+        Ok((board, turn))
+    }
+}
+
+/// A FEN does not say whether a king castled, so it is guessed for a
+/// side without castling rights from where its king and rooks stand:
+/// a king on g1 with a rook on f1 castled kingside, and a king on c1 or
+/// b1 with a rook on d1 castled queenside.
+fn castled(table: &[[Option<Piece>; 8]; 8], color: Color) -> Option<CastleSide> {
+    let back_rank = &table[color.back_rank() as usize];
+    let has = |file: usize, kind| back_rank[file] == Some(Piece { kind, color });
+    if has(6, Kind::King) && has(5, Kind::Rook) {
+        Some(CastleSide::Kingside)
+    } else if (has(2, Kind::King) || has(1, Kind::King)) && has(3, Kind::Rook) {
+        Some(CastleSide::Queenside)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn start_position_round_trip() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(Board::default().fen(Color::White), start);
+    let (board, turn) = Board::from_fen(start).unwrap();
+    assert_eq!(board.fen(turn), start);
+}
+
+#[test]
+fn castled_kings() {
+    let (board, _) = Board::from_fen("2kr3r/ppp2ppp/8/8/8/8/PPP2PPP/R4RK1 w - - 0 1").unwrap();
+    assert_eq!(board.white_castle.castled, Some(CastleSide::Kingside));
+    assert_eq!(board.black_castle.castled, Some(CastleSide::Queenside));
+    // a king that still has castling rights has not castled
+    let (board, _) = Board::from_fen("r4rk1/8/8/8/8/8/8/R4RK1 w Q - 0 1").unwrap();
+    assert_eq!(board.white_castle.castled, None);
+    assert_eq!(board.black_castle.castled, Some(CastleSide::Kingside));
+}
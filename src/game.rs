@@ -0,0 +1,334 @@
+use std::fmt::Display;
+
+use crate::fen::FenError;
+use crate::moves::Move;
+use crate::pgn::{Pgn, PgnError};
+use crate::piece::{Color, Kind};
+use crate::polyglot;
+use crate::table::Board;
+
+/// How a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Checkmate {
+        winner: Color,
+    },
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    /// The loser ran out of time. Games do not keep clocks, so
+    /// [`Game::result`] never returns it.
+    Timeout {
+        winner: Color,
+    },
+}
+
+impl Outcome {
+    /// The result as written in PGN, such as `1-0`.
+    pub fn result(self) -> &'static str {
+        match self {
+            Outcome::Checkmate {
+                winner: Color::White,
+            } => "1-0",
+            Outcome::Checkmate {
+                winner: Color::Black,
+            } => "0-1",
+            Outcome::Timeout {
+                winner: Color::White,
+            } => "1-0",
+            Outcome::Timeout {
+                winner: Color::Black,
+            } => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Checkmate { winner } => write!(f, "Checkmate, {winner:?} wins"),
+            Outcome::Stalemate => write!(f, "Stalemate"),
+            Outcome::Repetition => write!(f, "Draw by threefold repetition"),
+            Outcome::FiftyMoves => write!(f, "Draw by the fifty-move rule"),
+            Outcome::InsufficientMaterial => write!(f, "Draw by insufficient material"),
+            Outcome::Timeout { winner } => write!(f, "{winner:?} wins on time"),
+        }
+    }
+}
+
+/// A position reached in a game, with the clocks at that point.
+#[derive(Debug, Clone, Copy)]
+struct State {
+    board: Board,
+    turn: Color,
+    /// Plies since the last capture or pawn move.
+    halfmoves: u32,
+    fullmoves: u32,
+    /// The Polyglot key of the position.
+    key: u64,
+}
+
+/// A game in progress: the current position together with every move
+/// played and position reached since the start, so moves can be taken
+/// back and draws by repetition or the fifty-move rule are detected.
+#[derive(Debug, Clone)]
+pub struct Game {
+    /// The states before each move, the current one last.
+    states: Vec<State>,
+    moves: Vec<Move>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(Board::default(), Color::White)
+    }
+}
+
+impl Game {
+    pub fn new(board: Board, turn: Color) -> Self {
+        Game {
+            states: vec![State {
+                board,
+                turn,
+                halfmoves: 0,
+                fullmoves: 1,
+                key: polyglot::key(&board, turn),
+            }],
+            moves: vec![],
+        }
+    }
+
+    /// Starts a game from a FEN, including its halfmove clock and
+    /// fullmove number when present.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let (board, turn) = Board::from_fen(fen)?;
+        let mut game = Game::new(board, turn);
+        let mut clocks = fen
+            .split_whitespace()
+            .skip(4)
+            .map(str::parse);
+        let state = &mut game.states[0];
+        if let Some(Ok(halfmoves)) = clocks.next() {
+            state.halfmoves = halfmoves;
+        }
+        if let Some(Ok(fullmoves)) = clocks.next() {
+            state.fullmoves = fullmoves.max(1);
+        }
+        Ok(game)
+    }
+
+    /// Replays a game in PGN from its starting position.
+    pub fn from_pgn(pgn: &Pgn) -> Result<Self, PgnError> {
+        let plies = pgn.plies()?;
+        let mut game = Game::new(plies[0].board, plies[0].turn);
+        for ply in &plies[1..] {
+            game.push(
+                ply.last_move
+                    .expect("every ply after the first has a move"),
+            );
+        }
+        Ok(game)
+    }
+
+    fn state(&self) -> &State {
+        self.states
+            .last()
+            .expect("a game has at least one position")
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.state().board
+    }
+
+    pub fn turn(&self) -> Color {
+        self.state().turn
+    }
+
+    /// The moves played since the start of the game.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// The number of plies since the last capture or pawn move.
+    pub fn halfmoves(&self) -> u32 {
+        self.state().halfmoves
+    }
+
+    /// The number of the current move, starting at 1 and incremented
+    /// after each black move.
+    pub fn fullmoves(&self) -> u32 {
+        self.state().fullmoves
+    }
+
+    /// The FEN of the current position, with its clocks.
+    pub fn fen(&self) -> String {
+        let fen = self.board().fen(self.turn());
+        let fen = fen
+            .strip_suffix(" 0 1")
+            .unwrap_or(&fen);
+        format!("{fen} {} {}", self.halfmoves(), self.fullmoves())
+    }
+
+    /// Plays a move, which is assumed to be legal.
+    pub fn push(&mut self, r#move: Move) {
+        let state = *self.state();
+        let mut board = state.board;
+        let pawn = board[r#move.from].map(|piece| piece.kind) == Some(Kind::Pawn);
+        let capture = board[r#move.to].is_some();
+        board.apply(r#move);
+        let turn = state.turn.opposite();
+        self.states.push(State {
+            board,
+            turn,
+            halfmoves: if pawn || capture {
+                0
+            } else {
+                state.halfmoves + 1
+            },
+            fullmoves: state.fullmoves + (turn == Color::White) as u32,
+            key: polyglot::key(&board, turn),
+        });
+        self.moves.push(r#move);
+    }
+
+    /// Plays a move given in standard algebraic notation, or in
+    /// coordinate notation, if it is legal.
+    pub fn push_san(&mut self, san: &str) -> Option<Move> {
+        let r#move = self
+            .board()
+            .parse_san(san, self.turn())
+            .or_else(|| {
+                self.board()
+                    .parse_uci(san, self.turn())
+            })?;
+        self.push(r#move);
+        Some(r#move)
+    }
+
+    /// Takes back the last move.
+    pub fn pop(&mut self) -> Option<Move> {
+        let r#move = self.moves.pop()?;
+        self.states.pop();
+        Some(r#move)
+    }
+
+    /// Every position of the game from the start, the current one last.
+    pub fn positions(&self) -> impl DoubleEndedIterator<Item = (Board, Color)> + '_ {
+        self.states
+            .iter()
+            .map(|state| (state.board, state.turn))
+    }
+
+    /// The moves of the game in standard algebraic notation.
+    pub fn san(&self) -> Vec<String> {
+        self.states
+            .iter()
+            .zip(&self.moves)
+            .map(|(state, &r#move)| {
+                state
+                    .board
+                    .san(r#move, state.turn)
+            })
+            .collect()
+    }
+
+    /// How many times the current position has occurred. Only positions
+    /// since the last capture or pawn move can repeat it.
+    pub fn repetitions(&self) -> usize {
+        let key = self.state().key;
+        self.states
+            .iter()
+            .rev()
+            .take(self.halfmoves() as usize + 1)
+            .filter(|state| state.key == key)
+            .count()
+    }
+
+    /// How the game ended, `None` while it goes on.
+    pub fn result(&self) -> Option<Outcome> {
+        let (board, turn) = (self.board(), self.turn());
+        if board
+            .legal_moves(turn)
+            .next()
+            .is_none()
+        {
+            return Some(match board.in_check(turn) {
+                true => Outcome::Checkmate {
+                    winner: turn.opposite(),
+                },
+                false => Outcome::Stalemate,
+            });
+        }
+        if self.repetitions() >= 3 {
+            Some(Outcome::Repetition)
+        } else if self.halfmoves() >= 100 {
+            Some(Outcome::FiftyMoves)
+        } else if insufficient_material(board) {
+            Some(Outcome::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether neither side has enough material to checkmate: only kings,
+/// or a single minor piece against a lone king.
+pub fn insufficient_material(board: &Board) -> bool {
+    let pieces: Vec<_> = [Color::White, Color::Black]
+        .into_iter()
+        .flat_map(|color| board.colored_pieces(color))
+        .filter(|(piece, _)| piece.kind != Kind::King)
+        .collect();
+    match pieces[..] {
+        [] => true,
+        [(piece, _)] => matches!(piece.kind, Kind::Knight | Kind::Bishop),
+        _ => false,
+    }
+}
+
+#[test]
+fn push_and_pop() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K2R w K - 7 30").unwrap();
+    assert_eq!(game.halfmoves(), 7);
+    game.push_san("Rh2").unwrap();
+    game.push_san("Kd7").unwrap();
+    assert_eq!(game.fen(), "8/3k4/8/8/8/8/4P2R/4K3 w - - 9 31");
+    game.push_san("e4").unwrap();
+    assert_eq!((game.halfmoves(), game.fullmoves()), (0, 31));
+    assert_eq!(game.san(), ["Rh2", "Kd7", "e4"]);
+    assert_eq!(
+        game.pop()
+            .map(|m| m.to_string()),
+        Some("e2e4".into())
+    );
+    assert_eq!(game.fen(), "8/3k4/8/8/8/8/4P2R/4K3 w - - 9 31");
+    game.pop();
+    game.pop();
+    assert_eq!(game.pop(), None);
+    assert_eq!(game.positions().count(), 1);
+    assert_eq!(game.fen(), "4k3/8/8/8/8/8/4P3/4K2R w K - 7 30");
+}
+
+#[test]
+fn game_results() {
+    let mut game = Game::default();
+    for san in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1"] {
+        game.push_san(san).unwrap();
+        assert_eq!(game.result(), None);
+    }
+    game.push_san("Ng8").unwrap();
+    assert_eq!(game.repetitions(), 3);
+    assert_eq!(game.result(), Some(Outcome::Repetition));
+
+    let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K2R w K - 100 80").unwrap();
+    assert_eq!(game.result(), Some(Outcome::FiftyMoves));
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    assert_eq!(game.result(), None);
+    let mut game = game;
+    game.push_san("Ra8").unwrap();
+    let outcome = game.result().unwrap();
+    assert_eq!(outcome.result(), "1-0");
+    assert_eq!(outcome.to_string(), "Checkmate, White wins");
+}
@@ -0,0 +1,39 @@
+#![doc = include_str!("../readme.md")]
+pub mod book;
+pub mod clock;
+pub mod endgame;
+pub mod eval;
+pub mod fen;
+pub mod game;
+pub mod mcts;
+pub mod minimax;
+pub mod moves;
+pub mod notation;
+pub mod pawns;
+pub mod pgn;
+pub mod piece;
+pub mod polyglot;
+pub mod render;
+pub mod search;
+pub mod selfplay;
+pub mod settings;
+mod start_board;
+pub mod stats;
+pub mod svg;
+pub mod table;
+pub mod tree;
+pub mod tt;
+pub mod tune;
+pub mod uci;
+
+pub use eval::{evaluate, explain};
+pub use fen::FenError;
+pub use minimax::{Limits, Line, SearchResult};
+pub use moves::{Move, Play, Position};
+pub use pgn::Pgn;
+pub use piece::{Color, Kind, Piece};
+pub use render::RenderOptions;
+pub use search::{search, Searcher};
+pub use settings::EvalConfig;
+pub use svg::SvgOptions;
+pub use table::{Board, Castle, CastleSide};
@@ -1,23 +1,557 @@
-#![doc = include_str!("../readme.md")]
-#![allow(dead_code)]
-use piece::Color;
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chess::book::{Book, BookBuilder, BuildOptions, Rng};
+use chess::clock::Clock;
+use chess::game::{Game, Outcome};
+use chess::minimax::MATE;
+use chess::render::Panel;
+use chess::selfplay::{self, MatchOptions, Player, Score, Sprt};
+use chess::settings::Problem;
+use chess::stats::{IterationStats, SearchObserver};
+use chess::tt::{self, TranspositionTable};
+use chess::{
+    explain, search, Board, Color, EvalConfig, Limits, Move, Position, RenderOptions, Searcher,
+    SvgOptions,
+};
+use chess::{pgn, svg, tune, uci};
+use opt::{BookCommand, Command};
 use structopt::StructOpt;
-use table::Board;
-mod minimax;
-mod moves;
+
 mod opt;
-mod piece;
-mod settings;
-mod start_board;
-mod table;
 
 fn main() {
     let options = opt::Opt::from_args();
 
-    let board = Board::default();
-    let moves: Vec<_> = board
-        .moves(Color::White)
+    let result = match &options.command {
+        Some(Command::Svg(svg)) => export_svg(svg),
+        Some(Command::CheckSettings { path }) => check_settings(
+            path.as_ref()
+                .unwrap_or(&options.settings_path),
+        ),
+        Some(Command::Explain { fen }) => explain_position(&options, fen.as_deref()),
+        Some(Command::Analyze {
+            fen,
+            tree,
+            tree_depth,
+        }) => analyze(&options, fen.as_deref(), tree.as_deref(), *tree_depth),
+        Some(Command::Uci) => uci(&options),
+        Some(Command::Tune(cmd)) => tune_settings(&options, cmd),
+        Some(Command::Match(cmd)) => run_match(cmd),
+        Some(Command::Book(BookCommand::Build(cmd))) => build_book(cmd),
+        None => play(&options),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Plays a game against the engine in the terminal. Moves can be
+/// entered in algebraic or coordinate notation.
+fn play(options: &opt::Opt) -> Result<(), Box<dyn Error>> {
+    let human = if options.play_as_black {
+        Color::Black
+    } else {
+        Color::White
+    };
+    // the settings are validated before the game starts
+    let config = load_settings(options)?;
+    let limits = Limits {
+        depth: options.recursion_limit as u32,
+        time: options
+            .time_control
+            .is_none()
+            .then(|| Duration::from_millis(options.time_limit)),
+        threads: options.threads,
+        searcher: options.search,
+        observer: options
+            .verbose
+            .then(|| Arc::new(Verbose) as Arc<dyn SearchObserver>),
+        // the engine remembers what it searched on its previous moves
+        tt: Some(Arc::new(TranspositionTable::new(tt::DEFAULT_SIZE))),
+        ..Default::default()
+    };
+    let book = options
+        .book
+        .as_ref()
+        .map(Book::open)
+        .transpose()?;
+    let mut rng = Rng::from_time();
+    let mut game = Game::default();
+    let mut clocks = options
+        .time_control
+        .map(|control| [Clock::new(control); 2]);
+    let mut start = Instant::now();
+    let mut evaluation = None;
+    let mut lines = std::io::stdin()
+        .lock()
+        .lines();
+    loop {
+        let (board, turn) = (*game.board(), game.turn());
+        let render = RenderOptions {
+            orientation: human,
+            last_move: game.moves().last().copied(),
+            check: true,
+            panel: Some(Panel { turn, evaluation }),
+            ..Default::default()
+        };
+        println!("{}\n", board.render(render));
+
+        if let Some(outcome) = game.result() {
+            println!("{outcome}.");
+            return Ok(());
+        }
+        let side = match turn {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+
+        let r#move = if turn == human {
+            print!("Your move: ");
+            std::io::stdout().flush()?;
+            let Some(line) = lines.next() else {
+                return Ok(());
+            };
+            let line = line?;
+            let input = line.trim();
+            if input == "undo" {
+                // takes back the engine reply and the move before it
+                game.pop();
+                if game.turn() != human {
+                    game.pop();
+                }
+                continue;
+            }
+            match board
+                .parse_san(input, turn)
+                .or_else(|| board.parse_uci(input, turn))
+            {
+                Some(r#move) => r#move,
+                None => {
+                    println!("Illegal move: {input:?}");
+                    continue;
+                }
+            }
+        } else if let Some(r#move) = book
+            .as_ref()
+            .filter(|_| game.moves().len() < options.book_depth)
+            .and_then(|book| book.choose(&board, turn, options.book_selection, &mut rng))
+        {
+            println!("Engine plays {} (book)", board.san(r#move, turn));
+            r#move
+        } else {
+            let limits = Limits {
+                clock: clocks.map(|clocks| clocks[side].allocate()),
+                ..limits.clone()
+            };
+            let result = search(board, turn, limits, &config);
+            let r#move = result
+                .best_move
+                .ok_or("the engine did not find a move")?;
+            println!("Engine plays {}", board.san(r#move, turn));
+            evaluation = Some(match turn {
+                Color::White => result.score,
+                Color::Black => -result.score,
+            });
+            r#move
+        };
+        if let Some(clocks) = &mut clocks {
+            clocks[side].punch(start.elapsed());
+            if clocks[side].flagged() {
+                let winner = turn.opposite();
+                println!("{}.", Outcome::Timeout { winner });
+                return Ok(());
+            }
+            println!("White {} - Black {}", clocks[0], clocks[1]);
+        }
+        game.push(r#move);
+        start = Instant::now();
+    }
+}
+
+/// Reads the settings file, falling back to the built-in settings when
+/// the default file is not present.
+fn load_settings(options: &opt::Opt) -> Result<EvalConfig, Box<dyn Error>> {
+    let path = std::path::Path::new(&options.settings_path);
+    if !path.exists() && options.settings_path == opt::DEFAULT_SETTINGS_PATH {
+        return Ok(EvalConfig::default());
+    }
+    let (config, ignored) = EvalConfig::read(path)?;
+    warn(&ignored);
+    Ok(config)
+}
+
+fn check_settings(path: &str) -> Result<(), Box<dyn Error>> {
+    let (_, ignored) = EvalConfig::read(path)?;
+    warn(&ignored);
+    println!("{path}: ok");
+    Ok(())
+}
+
+fn warn(ignored: &[Problem]) {
+    for problem in ignored {
+        eprintln!("warning: {problem}");
+    }
+}
+
+fn explain_position(options: &opt::Opt, fen: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let config = load_settings(options)?;
+    let (board, turn) = match fen {
+        Some(fen) => Board::from_fen(fen)?,
+        None => (Board::default(), Color::White),
+    };
+    let render = RenderOptions {
+        panel: Some(Panel {
+            turn,
+            evaluation: None,
+        }),
+        ..Default::default()
+    };
+    println!("{}\n", board.render(render));
+    println!("{}", explain(&board, &config));
+    Ok(())
+}
+
+/// Prints the statistics of each iteration of the search.
+#[derive(Debug)]
+struct Verbose;
+
+impl SearchObserver for Verbose {
+    fn iteration(&self, stats: &IterationStats) {
+        println!(
+            "depth {}/{} score {:+.2} nodes {} (q {}) nps {} tt {:.1}% first-move cutoffs {:.1}% bf {} time {:.2}s pv {}",
+            stats.depth,
+            stats.seldepth,
+            stats.score,
+            stats.nodes,
+            stats.qnodes,
+            stats.nps(),
+            stats.tt_hit_rate() * 100.0,
+            stats.first_move_cutoff_rate() * 100.0,
+            stats
+                .branching_factor
+                .map_or("-".to_string(), |bf| format!("{bf:.2}")),
+            stats.elapsed.as_secs_f32(),
+            stats
+                .pv
+                .iter()
+                .map(|r#move| r#move.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+}
+
+/// The search limits given by the options.
+fn search_limits(options: &opt::Opt) -> Limits {
+    Limits {
+        depth: options.recursion_limit as u32,
+        time: Some(Duration::from_millis(options.time_limit)),
+        multi_pv: options.multi_pv,
+        threads: options.threads,
+        searcher: options.search,
+        observer: options
+            .verbose
+            .then(|| Arc::new(Verbose) as Arc<dyn SearchObserver>),
+        ..Default::default()
+    }
+}
+
+fn analyze(
+    options: &opt::Opt,
+    fen: Option<&str>,
+    tree: Option<&Path>,
+    tree_depth: u32,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_settings(options)?;
+    let (board, turn) = match fen {
+        Some(fen) => Board::from_fen(fen)?,
+        None => (Board::default(), Color::White),
+    };
+    let render = RenderOptions {
+        panel: Some(Panel {
+            turn,
+            evaluation: None,
+        }),
+        ..Default::default()
+    };
+    println!("{}\n", board.render(render));
+    let limits = Limits {
+        tree_depth: tree.map(|_| tree_depth),
+        ..search_limits(options)
+    };
+    let result = search(board, turn, limits, &config);
+    println!("Depth {}, {} nodes", result.depth, result.nodes);
+    for (i, line) in result
+        .lines
+        .iter()
+        .enumerate()
+    {
+        let score = if line.score.abs() > MATE - 500.0 {
+            let moves = ((MATE - line.score.abs()).round() as i32 + 1) / 2;
+            format!("#{}", moves * line.score.signum() as i32)
+        } else {
+            format!("{:+.2}", line.score)
+        };
+        let (mut board, mut turn) = (board, turn);
+        let pv: Vec<_> = line
+            .pv
+            .iter()
+            .map(|&r#move| {
+                let san = board.san(r#move, turn);
+                board.apply(r#move);
+                turn = turn.opposite();
+                san
+            })
+            .collect();
+        println!("{}. {score:>7} {}", i + 1, pv.join(" "));
+    }
+    if let Some(path) = tree {
+        let tree = result
+            .tree
+            .ok_or("the search kept no tree")?;
+        let dot = matches!(
+            path.extension()
+                .and_then(|extension| extension.to_str()),
+            Some("dot" | "gv")
+        );
+        let text = match dot {
+            true => tree.to_dot(),
+            false => serde_json::to_string_pretty(&tree.to_json())?,
+        };
+        std::fs::write(path, text)?;
+        println!("Search tree written to {}", path.display());
+    }
+    Ok(())
+}
+
+fn uci(options: &opt::Opt) -> Result<(), Box<dyn Error>> {
+    let config = load_settings(options)?;
+    // the limits come with each `go` command
+    let limits = Limits {
+        depth: Limits::default().depth,
+        time: None,
+        ..search_limits(options)
+    };
+    uci::run(
+        &config,
+        limits,
+        std::io::BufReader::new(std::io::stdin()),
+        std::io::stdout().lock(),
+    )?;
+    Ok(())
+}
+
+fn tune_settings(options: &opt::Opt, cmd: &opt::Tune) -> Result<(), Box<dyn Error>> {
+    let config = load_settings(options)?;
+    let samples = tune::parse_samples(&std::fs::read_to_string(&cmd.input)?)?;
+    if samples.is_empty() {
+        return Err("the input does not contain any position".into());
+    }
+    let k = tune::fit_k(&samples, &config);
+    println!(
+        "{} positions, K = {k:.3}, error {:.6}",
+        samples.len(),
+        tune::error(&samples, &config, k)
+    );
+    let options = tune::TuneOptions {
+        iterations: cmd.iterations,
+        step: cmd.step,
+    };
+    let tuned = tune::tune(&samples, &config, options, |iteration, error| {
+        println!("iteration {iteration}: error {error:.6}");
+    });
+    std::fs::write(&cmd.output, tuned.to_json())?;
+    println!("wrote {}", cmd.output.display());
+    Ok(())
+}
+
+fn run_match(cmd: &opt::Match) -> Result<(), Box<dyn Error>> {
+    let [first, second] = &cmd.settings[..] else {
+        return Err("expected two settings files".into());
+    };
+    let limits = Limits {
+        depth: cmd.recursion_limit,
+        time: cmd
+            .time_control
+            .is_none()
+            .then(|| Duration::from_millis(cmd.time_limit)),
+        ..Default::default()
+    };
+    let player = |path: &String, index: usize| -> Result<Player, Box<dyn Error>> {
+        let searcher = cmd
+            .search
+            .get(index)
+            .copied()
+            .unwrap_or_default();
+        Ok(Player {
+            name: match searcher {
+                Searcher::Minimax => path.clone(),
+                Searcher::Mcts => format!("{path} (mcts)"),
+            },
+            config: EvalConfig::from_file(path)?,
+            limits: Limits {
+                searcher,
+                ..limits.clone()
+            },
+        })
+    };
+    let (first, second) = (player(first, 0)?, player(second, 1)?);
+    let openings = match &cmd.openings {
+        Some(path) => selfplay::parse_openings(&std::fs::read_to_string(path)?)?,
+        None => vec![],
+    };
+    let sprt = Sprt {
+        elo0: cmd.elo0,
+        elo1: cmd.elo1,
+        alpha: cmd.alpha,
+        beta: cmd.beta,
+    };
+    let options = MatchOptions {
+        games: cmd.games,
+        threads: cmd.threads,
+        sprt: Some(sprt),
+        time_control: cmd.time_control,
+        ..Default::default()
+    };
+    let (score, games) =
+        selfplay::play_match(&first, &second, &openings, &options, |game, score| {
+            println!(
+                "game {} ({} vs {}): {}, score {}-{}-{}",
+                game.tag("Round")
+                    .unwrap_or("?"),
+                game.tag("White")
+                    .unwrap_or("?"),
+                game.tag("Black")
+                    .unwrap_or("?"),
+                game.result
+                    .as_deref()
+                    .unwrap_or("*"),
+                score.wins,
+                score.losses,
+                score.draws,
+            );
+        })?;
+    let text: Vec<_> = games
+        .iter()
+        .map(|game| game.to_string())
         .collect();
-    println!("{moves:?}");
-    
+    std::fs::write(&cmd.pgn, text.join("\n"))?;
+
+    println!(
+        "\nScore of {} vs {}: {} - {} - {} [{:.3}] {}",
+        first.name,
+        second.name,
+        score.wins,
+        score.losses,
+        score.draws,
+        score.ratio(),
+        score.games()
+    );
+    match score.elo() {
+        Some((elo, margin)) => println!("Elo difference: {elo:+.1} +/- {margin:.1}"),
+        None => println!("Elo difference: unknown"),
+    }
+    let (lower, upper) = Score::bounds(sprt);
+    println!(
+        "SPRT ({}, {}): LLR {:.2} [{lower:.2}, {upper:.2}], {}",
+        sprt.elo0,
+        sprt.elo1,
+        score.llr(sprt),
+        score.verdict(sprt)
+    );
+    Ok(())
+}
+
+fn build_book(cmd: &opt::BuildBook) -> Result<(), Box<dyn Error>> {
+    let text = std::fs::read_to_string(&cmd.pgn)?;
+    let options = BuildOptions {
+        max_ply: cmd.max_ply,
+        min_count: cmd.min_count,
+    };
+    let mut builder = BookBuilder::default();
+    let games = pgn::parse(&text);
+    let mut skipped = 0;
+    for (i, game) in games.iter().enumerate() {
+        // a broken game should not spoil the whole database
+        if let Err(err) = builder.add_game(game, options) {
+            eprintln!("skipping game {}: {err}", i + 1);
+            skipped += 1;
+        }
+    }
+    let book = builder.finish(options);
+    book.save(&cmd.output)?;
+    println!(
+        "{} games ({skipped} skipped), {} entries written to {}",
+        games.len(),
+        book.entries().len(),
+        cmd.output.display()
+    );
+    Ok(())
+}
+
+fn export_svg(cmd: &opt::Svg) -> Result<(), Box<dyn Error>> {
+    let square = |s: &str| {
+        s.parse::<Position>()
+            .map_err(|_| format!("invalid square: {s:?}"))
+    };
+    let arrows = cmd
+        .arrow
+        .iter()
+        .map(|arrow| {
+            if arrow.len() != 4 || !arrow.is_char_boundary(2) {
+                return Err(format!("invalid arrow: {arrow:?}"));
+            }
+            Ok(Move {
+                from: square(&arrow[..2])?,
+                to: square(&arrow[2..])?,
+                promotion: None,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let highlights = cmd
+        .highlight
+        .iter()
+        .map(|s| square(s))
+        .collect::<Result<_, _>>()?;
+    let options = SvgOptions {
+        light: cmd.light.clone(),
+        dark: cmd.dark.clone(),
+        coordinates: !cmd.no_coordinates,
+        orientation: if cmd.flip { Color::Black } else { Color::White },
+        square_size: cmd.square_size,
+        arrows,
+        highlights,
+        ..Default::default()
+    };
+
+    if let Some(path) = &cmd.pgn {
+        let text = std::fs::read_to_string(path)?;
+        let game = pgn::parse(&text)
+            .into_iter()
+            .next()
+            .ok_or("the PGN file does not contain any game")?;
+        std::fs::create_dir_all(&cmd.output)?;
+        for (ply, svg) in svg::pgn_svgs(&game, &options)?
+            .into_iter()
+            .enumerate()
+        {
+            std::fs::write(
+                cmd.output
+                    .join(format!("ply-{ply:03}.svg")),
+                svg,
+            )?;
+        }
+        return Ok(());
+    }
+    let board = match &cmd.fen {
+        Some(fen) => Board::from_fen(fen)?.0,
+        None => Board::default(),
+    };
+    std::fs::write(&cmd.output, board.svg(&options))?;
+    Ok(())
 }
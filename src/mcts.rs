@@ -0,0 +1,314 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::eval::evaluate_with;
+use crate::minimax::{Limits, Line, SearchResult, MATE};
+use crate::moves::Move;
+use crate::pawns::PawnHash;
+use crate::piece::Color;
+use crate::settings::EvalConfig;
+use crate::table::Board;
+
+/// How much the search tries moves it has not looked at much, rather than
+/// the ones that did best so far.
+const EXPLORATION: f32 = 1.4;
+
+/// A node of the Monte Carlo tree, kept in an arena and linked by index.
+/// Only the move is stored, the position is found again by playing the
+/// moves from the root, so the nodes stay small.
+struct Node {
+    /// The move that led to this node.
+    r#move: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    /// The sum of the results of the playouts through this node, from 0
+    /// to 1 each, for the side that played its move.
+    value: f32,
+}
+
+impl Node {
+    fn mean(&self) -> f32 {
+        self.value / self.visits.max(1) as f32
+    }
+}
+
+struct Tree<'a> {
+    board: Board,
+    turn: Color,
+    nodes: Vec<Node>,
+    config: &'a EvalConfig,
+    pawns: Option<PawnHash>,
+}
+
+/// Searches the position with Monte Carlo tree search: moves are picked
+/// down the tree by UCT, the tree grows by one position per playout, and
+/// a playout is the static evaluation of that position, turned into the
+/// expected result of the game.
+///
+/// The search stops after `limits.playouts` playouts, or earlier once its
+/// time or clock allows no more, or it is stopped. The depth and thread
+/// limits are ignored.
+pub fn search(board: Board, turn: Color, limits: &Limits, config: &EvalConfig) -> SearchResult {
+    let start = Instant::now();
+    let deadline = [
+        limits
+            .time
+            .map(|time| start + time),
+        limits
+            .clock
+            .map(|allocation| start + allocation.optimum),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    let mut tree = Tree {
+        board,
+        turn,
+        nodes: vec![Node {
+            r#move: None,
+            parent: None,
+            children: vec![],
+            expanded: false,
+            visits: 0,
+            value: 0.0,
+        }],
+        config,
+        pawns: (limits.pawn_hash > 0).then(|| PawnHash::new(limits.pawn_hash)),
+    };
+    let mut playouts = 0;
+    let mut seldepth = 0;
+    let stopped = || {
+        limits
+            .stop
+            .as_deref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    };
+    while playouts < limits.playouts.max(1) as u64
+        && deadline.is_none_or(|deadline| Instant::now() < deadline)
+        && !stopped()
+    {
+        let (leaf, depth, board, turn) = tree.select();
+        let result = tree.playout(&board, turn);
+        tree.backpropagate(leaf, result);
+        playouts += 1;
+        seldepth = seldepth.max(depth);
+    }
+
+    let mut lines: Vec<_> = tree.nodes[0]
+        .children
+        .iter()
+        .map(|&child| Line {
+            r#move: tree.nodes[child]
+                .r#move
+                .expect("children have a move"),
+            score: score(tree.nodes[child].mean()),
+            pv: tree.pv(child),
+        })
+        .collect();
+    // the most visited move is the best one
+    lines.sort_by_key(|line| {
+        std::cmp::Reverse(
+            tree.nodes[0]
+                .children
+                .iter()
+                .map(|&child| &tree.nodes[child])
+                .find(|child| child.r#move == Some(line.r#move))
+                .map_or(0, |child| child.visits),
+        )
+    });
+    lines.truncate(limits.multi_pv.max(1));
+    let best = lines.first();
+    SearchResult {
+        best_move: best.map(|line| line.r#move),
+        score: best.map_or(0.0, |line| line.score),
+        depth: seldepth,
+        pv: best
+            .map(|line| line.pv.clone())
+            .unwrap_or_default(),
+        nodes: playouts,
+        lines,
+        tree: None,
+    }
+}
+
+/// The score in pawns of an expected result, the inverse of the curve the
+/// evaluation is turned into results with. A certain win is a mate in one.
+fn score(mean: f32) -> f32 {
+    if mean >= 1.0 {
+        return MATE - 1.0;
+    }
+    let mean = mean.clamp(0.001, 0.999);
+    4.0 * (mean / (1.0 - mean)).log10()
+}
+
+impl Tree<'_> {
+    /// Walks down from the root to a node that has not been played out,
+    /// or to the end of the game, expanding the nodes it goes through for
+    /// the first time. Returns the node, its depth and its position with
+    /// the side to move.
+    fn select(&mut self) -> (usize, u32, Board, Color) {
+        let (mut board, mut turn) = (self.board, self.turn);
+        let mut node = 0;
+        let mut depth = 0;
+        loop {
+            // a node was played out when it was reached first, so the
+            // walk goes on to one of its children
+            if !self.nodes[node].expanded {
+                self.expand(node, &board, turn);
+            }
+            let parent = &self.nodes[node];
+            let Some(&child) = parent
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    self.uct(a, parent.visits)
+                        .total_cmp(&self.uct(b, parent.visits))
+                })
+            else {
+                // the game is over
+                return (node, depth, board, turn);
+            };
+            node = child;
+            depth += 1;
+            board.apply(
+                self.nodes[node]
+                    .r#move
+                    .expect("children have a move"),
+            );
+            turn = turn.opposite();
+            if self.nodes[node].visits == 0 {
+                return (node, depth, board, turn);
+            }
+        }
+    }
+
+    /// The upper confidence bound of the result of a node, infinite for
+    /// nodes never visited so that every move is tried once.
+    fn uct(&self, node: usize, parent_visits: u32) -> f32 {
+        let node = &self.nodes[node];
+        if node.visits == 0 {
+            return f32::INFINITY;
+        }
+        node.mean() + EXPLORATION * ((parent_visits as f32).ln() / node.visits as f32).sqrt()
+    }
+
+    fn expand(&mut self, node: usize, board: &Board, turn: Color) {
+        for r#move in board.legal_moves(turn) {
+            self.nodes.push(Node {
+                r#move: Some(r#move),
+                parent: Some(node),
+                children: vec![],
+                expanded: false,
+                visits: 0,
+                value: 0.0,
+            });
+            let index = self.nodes.len() - 1;
+            self.nodes[node]
+                .children
+                .push(index);
+        }
+        self.nodes[node].expanded = true;
+    }
+
+    /// The expected result of the position for the side that played the
+    /// move into it, `turn` being the side to move.
+    fn playout(&mut self, board: &Board, turn: Color) -> f32 {
+        if board
+            .legal_moves(turn)
+            .next()
+            .is_none()
+        {
+            return match board.in_check(turn) {
+                true => 1.0,
+                false => 0.5,
+            };
+        }
+        let score = evaluate_with(board, turn, self.config, self.pawns.as_mut());
+        1.0 - 1.0 / (1.0 + 10f32.powf(-score / 4.0))
+    }
+
+    fn backpropagate(&mut self, mut node: usize, mut result: f32) {
+        loop {
+            let current = &mut self.nodes[node];
+            current.visits += 1;
+            current.value += result;
+            result = 1.0 - result;
+            match current.parent {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+    }
+
+    /// The most visited line from a node, starting with its move.
+    fn pv(&self, mut node: usize) -> Vec<Move> {
+        let mut pv = vec![];
+        loop {
+            pv.extend(self.nodes[node].r#move);
+            let Some(&child) = self.nodes[node]
+                .children
+                .iter()
+                .filter(|&&child| self.nodes[child].visits > 0)
+                .max_by_key(|&&child| self.nodes[child].visits)
+            else {
+                return pv;
+            };
+            node = child;
+        }
+    }
+}
+
+#[test]
+fn mcts_finds_mate_in_one() {
+    let (board, turn) = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let limits = Limits {
+        playouts: 2000,
+        multi_pv: 2,
+        ..Default::default()
+    };
+    let result = search(board, turn, &limits, &EvalConfig::default());
+    assert_eq!(
+        result
+            .best_move
+            .map(|m| m.to_string()),
+        Some("a1a8".into())
+    );
+    assert_eq!(result.score, MATE - 1.0);
+    assert_eq!(result.nodes, 2000);
+    assert_eq!(result.lines.len(), 2);
+    assert!(result.lines[1].score < 10.0);
+}
+
+#[test]
+fn playouts_reach_new_positions() {
+    let config = EvalConfig::default();
+    let mut tree = Tree {
+        board: Board::default(),
+        turn: Color::White,
+        nodes: vec![Node {
+            r#move: None,
+            parent: None,
+            children: vec![],
+            expanded: false,
+            visits: 0,
+            value: 0.0,
+        }],
+        config: &config,
+        pawns: None,
+    };
+    for _ in 0..100 {
+        let (leaf, _, board, turn) = tree.select();
+        let result = tree.playout(&board, turn);
+        tree.backpropagate(leaf, result);
+    }
+    // far from the end of the game, no position is played out twice
+    assert_eq!(
+        tree.nodes[1..]
+            .iter()
+            .filter(|node| node.visits > 0)
+            .count(),
+        100
+    );
+}
@@ -1,127 +1,915 @@
+use crate::clock::{Allocation, TimeManager};
+use crate::eval::evaluate_with;
 use crate::moves::{Move, Play};
-use crate::settings::*;
-use crate::settings::{ATTACKED, DEFENDED};
-use crate::{piece::Color, table::Board};
+use crate::pawns::PawnHash;
+use crate::piece::{Color, Kind};
+use crate::search::Searcher;
+use crate::settings::EvalConfig;
+use crate::stats::{IterationStats, SearchObserver};
+use crate::tree::SearchTree;
+use crate::tt::{self, Bound, Entry, TranspositionTable};
+use crate::{polyglot, table::Board};
 
-use std::cell::RefCell;
-use std::collections::BinaryHeap;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use Color::*;
+/// The score of being checkmated at the root. Mates found deeper in
+/// the tree score slightly less, so shorter mates are preferred.
+pub const MATE: f32 = 10_000.0;
 
-struct MiniMaxNode {
+/// How many plies of captures the quiescence search may add below the
+/// depth of an iteration.
+const QUIESCENCE_PLIES: u32 = 8;
+
+/// A node of the search tree. Children are expanded lazily and kept
+/// between iterations of the iterative deepening, so the scores of the
+/// previous iteration can be used to order the moves of the next one.
+pub struct MiniMaxNode {
     turn: Color,
+    /// The static evaluation of the position, from the point of view of `turn`.
     heuristic: f32,
+    /// The result of the last search of this node, from the point of view of `turn`.
+    score: Cell<f32>,
     board: Board,
+    /// The Polyglot key of the position, for the transposition table.
+    key: u64,
+    /// The move that led to this node.
+    r#move: Option<Move>,
+    best: Cell<Option<Move>>,
     children: RefCell<Vec<MiniMaxNode>>,
+    /// How the node was last searched, for [`SearchResult::tree`].
+    trace: Cell<Trace>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Trace {
+    alpha: f32,
+    beta: f32,
+    depth: u32,
+    /// The iteration of the iterative deepening, from 1. Nodes never
+    /// searched have 0.
+    iteration: u32,
+    tt_hit: bool,
 }
 
 impl Ord for MiniMaxNode {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // SAFETY
-        // None is not a possible value
-        unsafe {
-            self.partial_cmp(other)
-                .unwrap_unchecked()
-        }
+        self.score
+            .get()
+            .total_cmp(&other.score.get())
     }
 }
 
-// if me and me {
-//     pick my highest chance
-// }
-// if me and opponent {
-//     pick my hightest change
-// }
-// if opponent and opponent {
-//     pick my lowest change
-// }
-
 impl PartialOrd for MiniMaxNode {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.heuristic
-            .partial_cmp(&other.heuristic)
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for MiniMaxNode {
     fn eq(&self, other: &Self) -> bool {
-        self.heuristic == other.heuristic
+        self.score.get() == other.score.get()
     }
 }
 impl Eq for MiniMaxNode {}
-impl Default for MiniMaxNode {
+
+/// How far the search may go, together with the tables it works with.
+/// The tables are given here rather than to a long-lived engine because
+/// the search is a plain function: whatever should outlive a search, like
+/// the transposition table of a game, is kept by the caller and passed in
+/// again with the limits of the next move.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// The maximum depth of the iterative deepening, in plies.
+    pub depth: u32,
+    /// The search stops after this time has elapsed, keeping the result
+    /// of the last completed iteration.
+    pub time: Option<Duration>,
+    /// The thinking time given by a clock, which the search extends
+    /// while the best move is unstable.
+    pub clock: Option<Allocation>,
+    /// The number of entries of the pawn hash table, 0 disables it. The
+    /// table is not thread safe, so each thread of each search makes its
+    /// own and only its size is given.
+    pub pawn_hash: usize,
+    /// The number of best moves whose exact score and principal variation
+    /// are searched for, see [`SearchResult::lines`].
+    pub multi_pv: usize,
+    /// The number of threads searching the position together, sharing
+    /// the transposition table. A single thread always finds the same
+    /// result.
+    pub threads: usize,
+    /// The transposition table, shared by the threads of the search and
+    /// by the searches of a game. Without one, each search makes its own
+    /// of [`tt::DEFAULT_SIZE`] entries.
+    pub tt: Option<Arc<TranspositionTable>>,
+    pub searcher: Searcher,
+    /// The most playouts of the Monte Carlo tree search.
+    pub playouts: u32,
+    /// Keeps the tree explored by the search down to this depth, see
+    /// [`SearchResult::tree`].
+    pub tree_depth: Option<u32>,
+    /// Receives the statistics of every iteration of the search.
+    pub observer: Option<Arc<dyn SearchObserver>>,
+    /// Stops the search once set, like running out of time, for callers
+    /// that decide when it ends from another thread.
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Limits {
     fn default() -> Self {
-        MiniMaxNode {
-            turn: Color::White,
-            heuristic: 0.0,
-            board: Board::default(),
-            children: Default::default(),
+        Limits {
+            depth: 4,
+            time: None,
+            clock: None,
+            pawn_hash: 1 << 14,
+            multi_pv: 1,
+            threads: 1,
+            tt: None,
+            searcher: Searcher::Minimax,
+            playouts: 20_000,
+            tree_depth: None,
+            observer: None,
+            stop: None,
         }
     }
 }
 
-impl MiniMaxNode {
-    fn reduce(&self) -> Move {
-        todo!()
-    }
-
-    fn new(board: Board, turn: Color, base_heuristic: f32) -> Self {
-        let player_score: f32 = board
-            .colored_pieces(turn)
-            .map(value)
-            .sum();
-        let opponent_score: f32 = board
-            .colored_pieces(turn.opposite())
-            .map(value)
-            .sum();
-        let rough_estimate = player_score - opponent_score;
-        MiniMaxNode {
-            turn,
-            heuristic: base_heuristic + player_score - opponent_score,
-            board,
-            children: Default::default(),
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    /// `None` if the side to move has no legal moves.
+    pub best_move: Option<Move>,
+    /// The score of the best move, from the point of view of the side to move.
+    pub score: f32,
+    /// The depth of the last completed iteration.
+    pub depth: u32,
+    /// The principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    /// The best moves of the position, as many as [`Limits::multi_pv`],
+    /// best first.
+    pub lines: Vec<Line>,
+    /// The tree of the last iteration down to [`Limits::tree_depth`].
+    pub tree: Option<SearchTree>,
+}
+
+/// A move of the root and the line expected to follow it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub r#move: Move,
+    /// The score of the move, from the point of view of the side to move.
+    pub score: f32,
+    /// The principal variation, starting with the move.
+    pub pv: Vec<Move>,
+}
+
+/// What happened during an iteration, for [`IterationStats`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    qnodes: u64,
+    tt_probes: u64,
+    tt_hits: u64,
+    cutoffs: u64,
+    first_move_cutoffs: u64,
+    seldepth: u32,
+}
+
+struct Context<'a> {
+    config: &'a EvalConfig,
+    deadline: Option<Instant>,
+    pawns: Option<PawnHash>,
+    tt: &'a TranspositionTable,
+    /// Set once the main thread is done, to stop the helper threads.
+    stop: &'a AtomicBool,
+    /// [`Limits::stop`].
+    cancel: Option<&'a AtomicBool>,
+    /// The nodes of every thread, which each adds its own to now and then.
+    total_nodes: &'a AtomicU64,
+    multi_pv: usize,
+    iteration: u32,
+    /// The quiescence search stops at this ply.
+    max_ply: u32,
+    nodes: u64,
+    /// The nodes already added to `total_nodes`.
+    counted: u64,
+    /// The counters of the current iteration.
+    counters: Counters,
+    aborted: bool,
+}
+
+impl Context<'_> {
+    fn out_of_time(&mut self) -> bool {
+        // checking the clock is expensive, so it is only done every few
+        // nodes, but not too few as expanding a node evaluates every child
+        if self.nodes.is_multiple_of(16) {
+            self.count_nodes();
+            self.aborted |= self
+                .deadline
+                .map(|deadline| Instant::now() >= deadline)
+                .unwrap_or(false);
+            self.aborted |= self
+                .stop
+                .load(AtomicOrdering::Relaxed);
+            self.aborted |= self
+                .cancel
+                .is_some_and(|cancel| cancel.load(AtomicOrdering::Relaxed));
         }
+        self.aborted
+    }
+
+    /// Adds the nodes searched since the last call to the total of the
+    /// threads.
+    fn count_nodes(&mut self) {
+        self.total_nodes
+            .fetch_add(self.nodes - self.counted, AtomicOrdering::Relaxed);
+        self.counted = self.nodes;
     }
-    fn add_children(&self) {
-        let plays = self.board.moves(self.turn);
-        let mut defended_value = 0.0f32;
-        let mut attacked_value = 0.0f32;
-        let mut children = vec![];
-        let mut count = 0;
-        for play in plays {
-            count += 1;
-            let r#move;
-            match play {
-                Play::Defense(move_, piece) => {
-                    defended_value += value((piece, move_.to));
-
-                    r#move = move_;
+
+    /// Searches the captures of a position until it is quiet, so it is not
+    /// evaluated in the middle of an exchange. The side to move can also
+    /// stand pat on the static evaluation `heuristic`. The positions are
+    /// not kept in the tree.
+    fn quiesce(
+        &mut self,
+        board: &Board,
+        turn: Color,
+        heuristic: f32,
+        ply: u32,
+        mut alpha: f32,
+        beta: f32,
+    ) -> f32 {
+        self.counters.seldepth = self
+            .counters
+            .seldepth
+            .max(ply);
+        if heuristic >= beta || ply >= self.max_ply {
+            return heuristic;
+        }
+        alpha = alpha.max(heuristic);
+        let mut captures: Vec<_> = board
+            .legal_plays(turn)
+            .filter_map(|play| match play {
+                Play::Capture(r#move, taken) => Some((r#move, worth(taken.kind))),
+                Play::Move(r#move) if r#move.promotion == Some(Kind::Queen) => Some((r#move, 0)),
+                _ => None,
+            })
+            .collect();
+        // the most valuable victims first, taken by the least valuable
+        // attackers
+        captures.sort_by_key(|&(r#move, taken)| {
+            let attacker = board[r#move.from].map_or(0, |piece| worth(piece.kind));
+            (std::cmp::Reverse(taken), attacker)
+        });
+        let mut best = heuristic;
+        for (r#move, _) in captures {
+            self.nodes += 1;
+            self.counters.qnodes += 1;
+            if self.out_of_time() {
+                break;
+            }
+            let mut child = *board;
+            child.apply(r#move);
+            let heuristic =
+                evaluate_with(&child, turn.opposite(), self.config, self.pawns.as_mut());
+            let score = -self.quiesce(&child, turn.opposite(), heuristic, ply + 1, -beta, -alpha);
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+/// The rough worth of a piece, to order captures.
+fn worth(kind: Kind) -> u8 {
+    match kind {
+        Kind::Pawn => 1,
+        Kind::Knight | Kind::Bishop => 3,
+        Kind::Rook => 5,
+        Kind::Queen => 9,
+        Kind::King => 100,
+    }
+}
+
+/// Searches the position with iterative deepening alpha-beta. The other
+/// searchers are dispatched by [`crate::search::search`].
+pub fn search(board: Board, turn: Color, limits: Limits, config: &EvalConfig) -> SearchResult {
+    let start = Instant::now();
+    let deadline = limits
+        .time
+        .map(|time| start + time);
+    let tt = limits
+        .tt
+        .clone()
+        .unwrap_or_else(|| Arc::new(TranspositionTable::new(tt::DEFAULT_SIZE)));
+    let stop = AtomicBool::new(false);
+    let nodes = AtomicU64::new(0);
+    let shared = Shared {
+        config,
+        limits: &limits,
+        tt: &tt,
+        stop: &stop,
+        nodes: &nodes,
+    };
+    std::thread::scope(|scope| {
+        // Lazy SMP: the helpers search the same position on their own and
+        // only help the main thread through the entries they leave in the
+        // transposition table. Half of them skip every other depth, so
+        // they are not all searching what the main thread is
+        let helpers: Vec<_> = (1..limits.threads.max(1))
+            .map(|thread| {
+                let shared = &shared;
+                scope.spawn(move || {
+                    shared.iterate(board, turn, 1 + thread as u32 % 2, deadline, None, false)
+                })
+            })
+            .collect();
+        let manager = limits
+            .clock
+            .map(|allocation| TimeManager::new(allocation, start));
+        let mut result = shared.iterate(board, turn, 1, deadline, manager, true);
+        stop.store(true, AtomicOrdering::Relaxed);
+        for helper in helpers {
+            let helper = helper
+                .join()
+                .expect("a search thread panicked");
+            result.nodes += helper.nodes;
+        }
+        result
+    })
+}
+
+/// What the threads of a search share.
+struct Shared<'a> {
+    config: &'a EvalConfig,
+    limits: &'a Limits,
+    tt: &'a TranspositionTable,
+    stop: &'a AtomicBool,
+    /// The nodes searched by all the threads.
+    nodes: &'a AtomicU64,
+}
+
+impl Shared<'_> {
+    /// The iterative deepening of one thread, from depth `first`. Only the
+    /// `main` thread keeps the tree and reports statistics.
+    fn iterate(
+        &self,
+        board: Board,
+        turn: Color,
+        first: u32,
+        deadline: Option<Instant>,
+        mut manager: Option<TimeManager>,
+        main: bool,
+    ) -> SearchResult {
+        let limits = self.limits;
+        let deadline = [
+            deadline,
+            manager
+                .as_ref()
+                .map(TimeManager::deadline),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let mut context = Context {
+            config: self.config,
+            deadline,
+            pawns: (limits.pawn_hash > 0).then(|| PawnHash::new(limits.pawn_hash)),
+            tt: self.tt,
+            stop: self.stop,
+            cancel: limits.stop.as_deref(),
+            total_nodes: self.nodes,
+            multi_pv: limits.multi_pv.max(1),
+            iteration: 0,
+            max_ply: 0,
+            nodes: 0,
+            counted: 0,
+            counters: Counters::default(),
+            aborted: false,
+        };
+        let tree_depth = limits
+            .tree_depth
+            .filter(|_| main);
+        let observer = limits
+            .observer
+            .as_deref()
+            .filter(|_| main);
+        let start = Instant::now();
+        let root = MiniMaxNode::new(board, turn, &mut context);
+        let mut result = SearchResult::default();
+        let mut last_nodes = None;
+        for depth in (first..=limits.depth.max(first)).step_by(first as usize) {
+            context.iteration += 1;
+            context.max_ply = depth + QUIESCENCE_PLIES;
+            context.counters = Counters::default();
+            let nodes = context.nodes;
+            let score = root.alpha_beta(depth, 0, -f32::INFINITY, f32::INFINITY, &mut context);
+            if context.aborted && depth > first {
+                break;
+            }
+            let nodes = context.nodes - nodes;
+            result = SearchResult {
+                best_move: root.best.get(),
+                score,
+                depth,
+                pv: root.pv(),
+                nodes: context.nodes,
+                lines: root.lines(context.multi_pv),
+                tree: tree_depth.map(|tree_depth| root.tree(tree_depth, context.iteration)),
+            };
+            if let Some(observer) = observer {
+                context.count_nodes();
+                let counters = context.counters;
+                observer.iteration(&IterationStats {
+                    depth,
+                    seldepth: counters.seldepth,
+                    score,
+                    pv: result.pv.clone(),
+                    nodes: self
+                        .nodes
+                        .load(AtomicOrdering::Relaxed),
+                    qnodes: counters.qnodes,
+                    elapsed: start.elapsed(),
+                    tt_probes: counters.tt_probes,
+                    tt_hits: counters.tt_hits,
+                    cutoffs: counters.cutoffs,
+                    first_move_cutoffs: counters.first_move_cutoffs,
+                    branching_factor: last_nodes.map(|last: u64| nodes as f32 / last.max(1) as f32),
+                });
+            }
+            last_nodes = Some(nodes);
+            if context.aborted || score.abs() >= MATE - depth as f32 {
+                break;
+            }
+            if let Some(manager) = &mut manager {
+                if !manager.next_iteration(result.best_move, score) {
+                    break;
                 }
-                Play::Capture(move_, piece) => {
-                    attacked_value += value((piece, move_.from));
-                    r#move = move_;
+            }
+        }
+        result.nodes = context.nodes;
+        result
+    }
+}
+
+/// Mate scores count the plies from the root, but the transposition
+/// table counts them from the position, which can be reached at any ply.
+fn to_tt(score: f32, ply: u32) -> f32 {
+    if score > MATE - 1000.0 {
+        score + ply as f32
+    } else if score < -MATE + 1000.0 {
+        score - ply as f32
+    } else {
+        score
+    }
+}
+
+fn from_tt(score: f32, ply: u32) -> f32 {
+    if score > MATE - 1000.0 {
+        score - ply as f32
+    } else if score < -MATE + 1000.0 {
+        score + ply as f32
+    } else {
+        score
+    }
+}
+
+impl MiniMaxNode {
+    /// The best move found by the last search of this node.
+    pub fn reduce(&self) -> Option<Move> {
+        self.best.get()
+    }
+
+    fn pv(&self) -> Vec<Move> {
+        let mut pv = vec![];
+        let children = self.children.borrow();
+        if let Some(best) = self.best.get() {
+            pv.push(best);
+            if let Some(child) = children
+                .iter()
+                .find(|child| child.r#move == Some(best))
+            {
+                pv.extend(child.pv());
+            }
+        }
+        pv
+    }
+
+    /// The best `count` moves of the last search, with their lines.
+    fn lines(&self, count: usize) -> Vec<Line> {
+        let mut children: Vec<_> = self
+            .children
+            .borrow()
+            .iter()
+            .map(|child| {
+                let r#move = child
+                    .r#move
+                    .expect("children have a move");
+                let mut pv = vec![r#move];
+                pv.extend(child.pv());
+                Line {
+                    r#move,
+                    score: -child.score.get(),
+                    pv,
                 }
-                Play::Move(move_) => {
-                    r#move = move_;
+            })
+            .collect();
+        children.sort_by(|a, b| b.score.total_cmp(&a.score));
+        // the best move comes first even if another one scored the same
+        if let Some(best) = children
+            .iter()
+            .position(|line| Some(line.r#move) == self.best.get())
+        {
+            let line = children.remove(best);
+            children.insert(0, line);
+        }
+        children.truncate(count);
+        children
+    }
+
+    /// The tree searched by `iteration`, down to `depth`. The children of
+    /// nodes that were pruned or found in the transposition table were not
+    /// searched, so they are left out.
+    fn tree(&self, depth: u32, iteration: u32) -> SearchTree {
+        let trace = self.trace.get();
+        let pruned = trace.iteration != iteration;
+        let children = if depth == 0 || pruned || trace.tt_hit {
+            vec![]
+        } else {
+            self.children
+                .borrow()
+                .iter()
+                .map(|child| child.tree(depth - 1, iteration))
+                .collect()
+        };
+        SearchTree {
+            r#move: self.r#move,
+            score: self.score.get(),
+            alpha: trace.alpha,
+            beta: trace.beta,
+            depth: trace.depth,
+            pruned,
+            tt_hit: trace.tt_hit,
+            children,
+        }
+    }
+
+    fn alpha_beta(
+        &self,
+        depth: u32,
+        ply: u32,
+        mut alpha: f32,
+        beta: f32,
+        context: &mut Context,
+    ) -> f32 {
+        context.nodes += 1;
+        context.counters.seldepth = context
+            .counters
+            .seldepth
+            .max(ply);
+        self.trace.set(Trace {
+            alpha,
+            beta,
+            depth,
+            iteration: context.iteration,
+            tt_hit: false,
+        });
+        if context.out_of_time() {
+            return self.heuristic;
+        }
+        if depth == 0 {
+            context.counters.qnodes += 1;
+            return context.quiesce(&self.board, self.turn, self.heuristic, ply, alpha, beta);
+        }
+        let entry = context.tt.probe(self.key);
+        context.counters.tt_probes += 1;
+        context.counters.tt_hits += entry.is_some() as u64;
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = from_tt(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                self.trace.set(Trace {
+                    tt_hit: true,
+                    ..self.trace.get()
+                });
+                self.best.set(entry.r#move);
+                self.score.set(score);
+                return score;
+            }
+        }
+        if self
+            .children
+            .borrow()
+            .is_empty()
+        {
+            self.add_children(context);
+        }
+        let mut children = self.children.borrow_mut();
+        if children.is_empty() {
+            let score = if self.board.in_check(self.turn) {
+                -MATE + ply as f32
+            } else {
+                0.0
+            };
+            self.score.set(score);
+            return score;
+        }
+        // the children are sorted by their own score, so the
+        // best replies for this node come first.
+        children.sort();
+        // the move found best before, maybe by another thread, goes first
+        if let Some(first) = entry
+            .and_then(|entry| entry.r#move)
+            .and_then(|r#move| {
+                children
+                    .iter()
+                    .position(|child| child.r#move == Some(r#move))
+            })
+        {
+            children[..=first].rotate_right(1);
+        }
+        let alpha_start = alpha;
+        let mut best = -f32::INFINITY;
+        // with several lines at the root, a move is only cut off when it
+        // cannot enter them, so the alpha is the score of the last one
+        let mut lines = vec![];
+        for (i, child) in children.iter().enumerate() {
+            let score = -child.alpha_beta(depth - 1, ply + 1, -beta, -alpha, context);
+            child.score.set(-score);
+            if context.aborted {
+                return best;
+            }
+            if score > best {
+                best = score;
+                self.best.set(child.r#move);
+            }
+            if ply == 0 && context.multi_pv > 1 {
+                lines.push(score);
+                lines.sort_by(|a: &f32, b| b.total_cmp(a));
+                if let Some(&last) = lines.get(context.multi_pv - 1) {
+                    alpha = alpha.max(last);
                 }
-                _ => panic!("castle not implemented"),
+            } else {
+                alpha = alpha.max(score);
             }
-            let mut board = self.board;
-            board.apply(r#move);
+            if alpha >= beta {
+                context.counters.cutoffs += 1;
+                context
+                    .counters
+                    .first_move_cutoffs += (i == 0) as u64;
+                break;
+            }
+        }
+        self.score.set(best);
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best <= alpha_start {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        context.tt.store(
+            self.key,
+            Entry {
+                score: to_tt(best, ply),
+                depth,
+                bound,
+                r#move: self.best.get(),
+            },
+        );
+        best
+    }
 
-            children.push(Self::new(
-                board,
-                self.turn.opposite(),
-                heuristic(defended_value, attacked_value, count),
-            ));
+    fn new(board: Board, turn: Color, context: &mut Context) -> Self {
+        let heuristic = evaluate_with(&board, turn, context.config, context.pawns.as_mut());
+        MiniMaxNode {
+            turn,
+            heuristic,
+            score: Cell::new(heuristic),
+            board,
+            key: polyglot::key(&board, turn),
+            r#move: None,
+            best: Cell::new(None),
+            children: Default::default(),
+            trace: Default::default(),
         }
-        let mut cell = self.children.borrow_mut();
-        *cell = children;
     }
+
+    fn add_children(&self, context: &mut Context) {
+        let children = self
+            .board
+            .legal_moves(self.turn)
+            .map(|r#move| {
+                let mut board = self.board;
+                board.apply(r#move);
+                let mut child = Self::new(board, self.turn.opposite(), context);
+                child.r#move = Some(r#move);
+                child
+            })
+            .collect();
+        *self.children.borrow_mut() = children;
+    }
+}
+
+#[test]
+fn finds_mate_in_one() {
+    let (board, turn) = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let result = search(
+        board,
+        turn,
+        Limits {
+            depth: 3,
+            ..Default::default()
+        },
+        &EvalConfig::default(),
+    );
+    assert_eq!(
+        result
+            .best_move
+            .map(|m| m.to_string()),
+        Some("a1a8".into())
+    );
+    assert!(result.score >= MATE - 1.0);
+}
+
+#[test]
+fn multi_pv_lines() {
+    let (board, turn) = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let limits = Limits {
+        depth: 2,
+        multi_pv: 3,
+        ..Default::default()
+    };
+    let result = search(board, turn, limits, &EvalConfig::default());
+    assert_eq!(result.lines.len(), 3);
+    assert_eq!(result.lines[0].pv, result.pv);
+    assert_eq!(result.lines[0].score, result.score);
+    assert!(result.lines[1].score <= result.lines[0].score);
+    assert!(result.lines[2].score <= result.lines[1].score);
+    assert!(result.lines[1].score > -MATE / 2.0);
+    assert_eq!(result.lines[1].pv.len(), 2);
+}
+
+#[test]
+fn parallel_search() {
+    let (board, turn) =
+        Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 2 3")
+            .unwrap();
+    let limits = Limits {
+        depth: 3,
+        ..Default::default()
+    };
+    let config = EvalConfig::default();
+    // a single thread is deterministic
+    let first = search(board, turn, limits.clone(), &config);
+    let second = search(board, turn, limits.clone(), &config);
+    assert_eq!(
+        (first.best_move, first.nodes),
+        (second.best_move, second.nodes)
+    );
+    assert_eq!(first.pv, second.pv);
+    let limits = Limits {
+        threads: 4,
+        ..limits
+    };
+    let result = search(board, turn, limits, &config);
+    assert_eq!(
+        result
+            .best_move
+            .map(|m| m.to_string()),
+        Some("f3f7".into())
+    );
+    assert!(result.score >= MATE - 1.0);
+    assert!(result.nodes > first.nodes / 2);
+}
+
+#[test]
+fn shared_transposition_table() {
+    let (board, turn) =
+        Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .unwrap();
+    let limits = Limits {
+        depth: 3,
+        tt: Some(Arc::new(TranspositionTable::new(tt::DEFAULT_SIZE))),
+        ..Default::default()
+    };
+    let config = EvalConfig::default();
+    let first = search(board, turn, limits.clone(), &config);
+    // the second search finds the positions of the first one
+    let second = search(board, turn, limits.clone(), &config);
+    assert!(second.nodes < first.nodes);
+    limits
+        .tt
+        .as_ref()
+        .unwrap()
+        .clear();
+    let third = search(board, turn, limits, &config);
+    assert_eq!(third.nodes, first.nodes);
+}
+
+#[test]
+fn search_tree() {
+    let (board, turn) =
+        Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .unwrap();
+    let limits = Limits {
+        depth: 3,
+        tree_depth: Some(3),
+        ..Default::default()
+    };
+    let result = search(board, turn, limits, &EvalConfig::default());
+    let tree = result.tree.unwrap();
+    assert_eq!((tree.r#move, tree.depth), (None, 3));
+    assert_eq!(tree.score, result.score);
+    let best = tree
+        .children
+        .iter()
+        .find(|child| child.r#move == result.best_move)
+        .unwrap();
+    assert!(!best.pruned);
+    assert_eq!(best.score, -result.score);
+    // the replies to worse moves are cut off once one refutes them, so
+    // the tree stops at them while the searched replies go on
+    let replies: Vec<(Vec<&SearchTree>, Vec<&SearchTree>)> = tree
+        .children
+        .iter()
+        .map(|child| {
+            child
+                .children
+                .iter()
+                .partition(|reply| reply.pruned)
+        })
+        .collect();
+    assert!(replies
+        .iter()
+        .flat_map(|(pruned, _)| pruned)
+        .all(|reply| reply.children.is_empty()));
+    assert!(replies
+        .iter()
+        .any(|(pruned, searched)| !pruned.is_empty()
+            && searched
+                .iter()
+                .any(|reply| !reply.children.is_empty())));
+    assert_eq!(tree.to_json()["children"][0]["depth"], 2);
+    assert!(tree
+        .to_dot()
+        .contains("n0 -> n1;"));
+}
+
+#[test]
+fn search_statistics() {
+    let (board, turn) =
+        Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .unwrap();
+    let stats = Arc::new(std::sync::Mutex::new(vec![]));
+    let limits = Limits {
+        depth: 3,
+        observer: Some(stats.clone()),
+        ..Default::default()
+    };
+    let result = search(board, turn, limits, &EvalConfig::default());
+    let stats = stats.lock().unwrap();
+    assert_eq!(
+        stats
+            .iter()
+            .map(|stats| stats.depth)
+            .collect::<Vec<_>>(),
+        [1, 2, 3]
+    );
+    let last = stats.last().unwrap();
+    assert_eq!((last.nodes, last.score), (result.nodes, result.score));
+    assert_eq!(last.pv, result.pv);
+    // the captures are searched past the depth
+    assert!((4..=3 + QUIESCENCE_PLIES).contains(&last.seldepth));
+    assert!(last.qnodes > 0 && last.qnodes < last.nodes);
+    assert!(last.cutoffs > 0);
+    assert!((0.0..=1.0).contains(&last.first_move_cutoff_rate()));
+    assert!(last.tt_probes > 0);
+    assert_eq!(stats[0].branching_factor, None);
+    assert!(last.branching_factor.unwrap() > 1.0);
 }
 
-fn heuristic(defended_value: f32, attacked_value: f32, count: i32) -> f32 {
-    return *DEFENDED * defended_value
-        + *ATTACKED * attacked_value
-        + *AVAILABLE_MOVES * count as f32;
+#[test]
+fn quiescence_sees_recaptures() {
+    // the pawn on d5 is defended, so taking it loses the queen
+    let (board, turn) = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    let limits = Limits {
+        depth: 1,
+        ..Default::default()
+    };
+    let result = search(board, turn, limits, &EvalConfig::default());
+    assert_ne!(
+        result
+            .best_move
+            .map(|m| m.to_string()),
+        Some("d1d5".into())
+    );
 }
@@ -1,10 +1,14 @@
-use crate::piece::Piece;
+use crate::piece::{Kind, Piece};
+use std::fmt::Display;
+use std::str::FromStr;
 
 /// A move represents the change of position of a piece.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Move {
     pub to: Position,
     pub from: Position,
+    /// The kind a pawn turns into when it reaches the last rank.
+    pub promotion: Option<Kind>,
 }
 /// A play is wrapper around a move that carries more information.
 /// Not all plays can actually be played, i.e., `Defense` does
@@ -12,19 +16,48 @@ pub struct Move {
 /// maybe in future turns. This information about non playable moves
 /// is kept because it can be useful for heuristics and it would be
 /// expensive to recompute if it is needed.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Play {
     Defense(Move, Piece),
     Capture(Move, Piece),
     Move(Move),
-    RightCastle,
-    LeftClastle,
+    /// Castling towards the h-file, represented by the king's move.
+    RightCastle(Move),
+    /// Castling towards the a-file, represented by the king's move.
+    LeftCastle(Move),
 }
 
 pub fn playable(r#move: Play) -> bool {
     !matches!(r#move, Play::Defense(_, _))
 }
 
+impl Play {
+    pub fn r#move(self) -> Move {
+        match self {
+            Play::Defense(r#move, _)
+            | Play::Capture(r#move, _)
+            | Play::Move(r#move)
+            | Play::RightCastle(r#move)
+            | Play::LeftCastle(r#move) => r#move,
+        }
+    }
+}
+
+impl Display for Move {
+    /// Formats the move in coordinate notation, e.g. `e7e8q`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(kind) = self.promotion {
+            let piece = Piece {
+                kind,
+                color: crate::piece::Color::Black,
+            };
+            write!(f, "{}", piece.symbol())?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Copy, Hash, PartialEq, Eq)]
 pub struct Position {
     pub rank: isize,
@@ -32,8 +65,12 @@ pub struct Position {
 }
 
 impl Position {
+    pub fn in_bounds(self) -> bool {
+        (0..8).contains(&self.rank) && (0..8).contains(&self.file)
+    }
+
     pub fn relative<'a>(self, pos: &'a [(isize, isize)]) -> impl Iterator<Item = Self> + 'a {
-        pos.into_iter()
+        pos.iter()
             .copied()
             .map(|x| (x.0, x.1))
             .map(Position::from)
@@ -50,3 +87,25 @@ impl From<(isize, isize)> for Position {
         }
     }
 }
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file = (b'a' + self.file as u8) as char;
+        let rank = (b'8' - self.rank as u8) as char;
+        write!(f, "{file}{rank}")
+    }
+}
+
+impl FromStr for Position {
+    type Err = ();
+    /// Parses a square in algebraic notation, e.g. `e4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Position {
+                rank: (b'8' - rank) as isize,
+                file: (file - b'a') as isize,
+            }),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::moves::{Move, Position};
+use crate::piece::{Color, Kind, Piece};
+use crate::table::Board;
+use Kind::*;
+
+fn letter(kind: Kind) -> char {
+    Piece {
+        kind,
+        color: Color::White,
+    }
+    .symbol()
+}
+
+impl Board {
+    /// Formats a legal move in standard algebraic notation, e.g. `Nbd7`,
+    /// `exd5`, `O-O` or `e8=Q#`.
+    pub fn san(&self, r#move: Move, turn: Color) -> String {
+        let Some(piece) = self[r#move.from] else {
+            return r#move.to_string();
+        };
+        let mut san = String::new();
+        if piece.kind == King && (r#move.to.file - r#move.from.file).abs() == 2 {
+            san.push_str(if r#move.to.file == 6 { "O-O" } else { "O-O-O" });
+        } else {
+            let capture = self[r#move.to].is_some()
+                || (piece.kind == Pawn && r#move.to.file != r#move.from.file);
+            if piece.kind == Pawn {
+                if capture {
+                    san.push((b'a' + r#move.from.file as u8) as char);
+                }
+            } else {
+                san.push(letter(piece.kind));
+                let rivals: Vec<_> = self
+                    .legal_moves(turn)
+                    .filter(|other| other.to == r#move.to && other.from != r#move.from)
+                    .filter(|other| self[other.from] == Some(piece))
+                    .collect();
+                if !rivals.is_empty() {
+                    let square = r#move.from.to_string();
+                    if rivals
+                        .iter()
+                        .all(|other| other.from.file != r#move.from.file)
+                    {
+                        san.push_str(&square[..1]);
+                    } else if rivals
+                        .iter()
+                        .all(|other| other.from.rank != r#move.from.rank)
+                    {
+                        san.push_str(&square[1..]);
+                    } else {
+                        san.push_str(&square);
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&r#move.to.to_string());
+            if let Some(kind) = r#move.promotion {
+                san.push('=');
+                san.push(letter(kind));
+            }
+        }
+        let mut board = *self;
+        board.apply(r#move);
+        if board.in_check(turn.opposite()) {
+            let mate = board
+                .legal_moves(turn.opposite())
+                .next()
+                .is_none();
+            san.push(if mate { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// Parses a move in standard algebraic notation. Annotations like `+`, `#`,
+    /// `!` or `?` are ignored, and over-disambiguated moves are accepted.
+    pub fn parse_san(&self, san: &str, turn: Color) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let rank = turn.back_rank();
+        let castle = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle {
+            let from = Position::from((rank, 4));
+            let to = Position::from((rank, file));
+            return self
+                .legal_moves(turn)
+                .find(|m| {
+                    m.from == from && m.to == to && self[from].map(|p| p.kind) == Some(King)
+                });
+        }
+
+        let (san, promotion) = match san.split_once('=') {
+            Some((san, kind)) => (san, Some(Piece::from_symbol(kind.chars().next()?)?.kind)),
+            None => (san, None),
+        };
+        let mut chars: Vec<char> = san
+            .chars()
+            .filter(|&c| c != 'x')
+            .collect();
+        let kind = match chars.first()? {
+            c if c.is_ascii_uppercase() => {
+                let kind = Piece::from_symbol(*c)?.kind;
+                chars.remove(0);
+                kind
+            }
+            _ => Pawn,
+        };
+        if chars.len() < 2 {
+            return None;
+        }
+        let to: Position = chars[chars.len() - 2..]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()?;
+        let hints = &chars[..chars.len() - 2];
+        let file_hint = hints
+            .iter()
+            .find(|c| c.is_ascii_lowercase())
+            .map(|&c| (c as u8 - b'a') as isize);
+        let rank_hint = hints
+            .iter()
+            .find(|c| c.is_ascii_digit())
+            .map(|&c| (b'8' - c as u8) as isize);
+
+        self.legal_moves(turn)
+            .find(|m| {
+                m.to == to
+                    && m.promotion == promotion
+                    && self[m.from].map(|p| p.kind) == Some(kind)
+                    && file_hint
+                        .map(|file| m.from.file == file)
+                        .unwrap_or(true)
+                    && rank_hint
+                        .map(|rank| m.from.rank == rank)
+                        .unwrap_or(true)
+            })
+    }
+
+    /// Parses a move in coordinate notation, e.g. `e2e4` or `e7e8q`,
+    /// returning it only if it is legal.
+    pub fn parse_uci(&self, uci: &str, turn: Color) -> Option<Move> {
+        self.legal_moves(turn)
+            .find(|m| m.to_string() == uci)
+    }
+}
+
+#[test]
+fn san_round_trip() {
+    let (board, turn) =
+        Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .unwrap();
+    for r#move in board.legal_moves(turn) {
+        let san = board.san(r#move, turn);
+        assert_eq!(board.parse_san(&san, turn), Some(r#move), "{san}");
+    }
+    let castle = board
+        .parse_san("O-O-O", turn)
+        .unwrap();
+    assert_eq!(board.san(castle, turn), "O-O-O");
+}
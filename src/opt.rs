@@ -1,7 +1,13 @@
-use std::default;
+use std::path::PathBuf;
 
+use chess::book::Selection;
+use chess::clock::TimeControl;
+use chess::svg::SvgColor;
+use chess::Searcher;
 use structopt::StructOpt;
 
+pub const DEFAULT_SETTINGS_PATH: &str = "./settings.json";
+
 #[derive(Debug, StructOpt)]
 pub struct Opt {
     #[structopt(short = "r", long, default_value = "256")]
@@ -10,10 +16,181 @@ pub struct Opt {
     pub time_limit: u64,
     #[structopt(short = "b", long)]
     pub play_as_black: bool,
-    #[structopt(short = "s", long, default_value = "./settings.json")]
+    #[structopt(short = "s", long, default_value = DEFAULT_SETTINGS_PATH)]
     pub settings_path: String,
+    /// A Polyglot opening book the engine plays from.
+    #[structopt(long, parse(from_os_str))]
+    pub book: Option<PathBuf>,
+    /// The book is only used for this many plies from the start.
+    #[structopt(long, default_value = "16")]
+    pub book_depth: usize,
+    /// How book moves are picked: `weighted` at random or the `best` one.
+    #[structopt(long, default_value = "weighted")]
+    pub book_selection: Selection,
+    /// Plays with clocks, as `[moves/]seconds[+increment][d delay]`, so
+    /// `40/300+2` is 5 minutes for 40 moves with 2 seconds per move.
+    /// Replaces `--time-limit`.
+    #[structopt(long)]
+    pub time_control: Option<TimeControl>,
+    /// The number of best moves searched for by `analyze` and `uci`.
+    #[structopt(long, default_value = "1")]
+    pub multi_pv: usize,
+    /// The number of threads searching each position.
+    #[structopt(long, default_value = "1")]
+    pub threads: usize,
+    /// How the engine searches: `minimax` or `mcts`.
+    #[structopt(long, default_value = "minimax")]
+    pub search: Searcher,
+    /// Prints the statistics of every iteration of the search.
+    #[structopt(short = "v", long)]
+    pub verbose: bool,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Exports SVG diagrams of a position, or of every ply of a PGN game.
+    Svg(Svg),
+    /// Validates a settings file and reports every problem found.
+    CheckSettings {
+        /// The file to check, defaults to `--settings-path`.
+        path: Option<String>,
+    },
+    /// Prints every term of the evaluation of a position for each side.
+    Explain {
+        /// The position to explain, defaults to the starting position.
+        fen: Option<String>,
+    },
+    /// Searches a position and prints the best `--multi-pv` moves with
+    /// their scores and principal variations.
+    Analyze {
+        /// The position to analyze, defaults to the starting position.
+        fen: Option<String>,
+        /// Writes the tree explored by the last iteration to this file, as
+        /// Graphviz DOT if it ends in `.dot` or `.gv` and JSON otherwise.
+        #[structopt(long, parse(from_os_str))]
+        tree: Option<PathBuf>,
+        /// How deep the written tree goes.
+        #[structopt(long, default_value = "2")]
+        tree_depth: u32,
+    },
+    /// Talks the Universal Chess Interface on the standard input and output.
+    Uci,
+    /// Tunes the evaluation weights on positions labeled with the results
+    /// of their games, starting from `--settings-path`.
+    Tune(Tune),
+    /// Plays the engine against itself with two settings files and
+    /// reports the Elo difference between them.
+    Match(Match),
+    /// Works with Polyglot opening books.
+    Book(BookCommand),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum BookCommand {
+    /// Builds a book from the games of a PGN database.
+    Build(BuildBook),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BuildBook {
+    #[structopt(parse(from_os_str))]
+    pub pgn: PathBuf,
+    #[structopt(short = "o", long, parse(from_os_str), default_value = "book.bin")]
+    pub output: PathBuf,
+    /// Only the moves of the first plies of each game are counted.
+    #[structopt(long, default_value = "20")]
+    pub max_ply: usize,
+    /// Moves played fewer times than this are left out.
+    #[structopt(long, default_value = "2")]
+    pub min_count: u32,
 }
 
-pub fn options() -> Opt {
-    Opt::from_args()
+#[derive(Debug, StructOpt)]
+pub struct Match {
+    /// The settings of the two engines, the first one is the one tested.
+    #[structopt(short = "s", long = "settings-path", required = true)]
+    pub settings: Vec<String>,
+    /// A PGN database, or a file with one FEN or EPD position per line.
+    /// Defaults to the starting position.
+    #[structopt(long, parse(from_os_str))]
+    pub openings: Option<PathBuf>,
+    #[structopt(short = "n", long, default_value = "100")]
+    pub games: usize,
+    /// The number of games played at the same time.
+    #[structopt(short = "j", long, default_value = "1")]
+    pub threads: usize,
+    #[structopt(short = "r", long, default_value = "256")]
+    pub recursion_limit: u32,
+    /// The thinking time per move, in milliseconds.
+    #[structopt(short = "t", long, default_value = "100")]
+    pub time_limit: u64,
+    /// The clock of each engine, as `[moves/]seconds[+increment][d delay]`.
+    /// Replaces `--time-limit`.
+    #[structopt(long)]
+    pub time_control: Option<TimeControl>,
+    /// How each engine searches, `minimax` or `mcts`, in the order of
+    /// the settings. Engines without one use minimax.
+    #[structopt(long)]
+    pub search: Vec<Searcher>,
+    /// Where all the games are saved.
+    #[structopt(long, parse(from_os_str), default_value = "match.pgn")]
+    pub pgn: PathBuf,
+    /// The Elo difference of the null hypothesis of the SPRT.
+    #[structopt(long, default_value = "0")]
+    pub elo0: f64,
+    /// The Elo difference of the alternative hypothesis of the SPRT.
+    #[structopt(long, default_value = "5")]
+    pub elo1: f64,
+    #[structopt(long, default_value = "0.05")]
+    pub alpha: f64,
+    #[structopt(long, default_value = "0.05")]
+    pub beta: f64,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Tune {
+    /// An EPD or CSV file of positions and game results.
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+    /// Where the tuned settings are written.
+    #[structopt(short = "o", long, parse(from_os_str), default_value = "tuned.json")]
+    pub output: PathBuf,
+    /// The maximum number of passes over all the weights.
+    #[structopt(long, default_value = "10")]
+    pub iterations: usize,
+    /// How much each weight is moved at a time, in pawns.
+    #[structopt(long, default_value = "0.05")]
+    pub step: f32,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Svg {
+    /// The position to draw, defaults to the starting position.
+    #[structopt(long, conflicts_with = "pgn")]
+    pub fen: Option<String>,
+    /// Draws the first game of this file, one diagram per ply.
+    #[structopt(long, parse(from_os_str))]
+    pub pgn: Option<PathBuf>,
+    /// The output file, or the output directory when drawing a PGN game.
+    #[structopt(short = "o", long, parse(from_os_str), default_value = "board.svg")]
+    pub output: PathBuf,
+    /// Draws the board from black's point of view.
+    #[structopt(long)]
+    pub flip: bool,
+    #[structopt(long)]
+    pub no_coordinates: bool,
+    #[structopt(long, default_value = "#f0d9b5")]
+    pub light: SvgColor,
+    #[structopt(long, default_value = "#b58863")]
+    pub dark: SvgColor,
+    #[structopt(long, default_value = "45")]
+    pub square_size: u32,
+    /// Arrows in coordinate notation, e.g. `e2e4`.
+    #[structopt(long)]
+    pub arrow: Vec<String>,
+    /// Squares to highlight, e.g. `e4`.
+    #[structopt(long)]
+    pub highlight: Vec<String>,
 }
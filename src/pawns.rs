@@ -0,0 +1,266 @@
+use crate::moves::Position;
+use crate::piece::{Color, Kind};
+use crate::settings::EvalConfig;
+use crate::table::Board;
+
+/// The pawns of both sides as bitboards, with one bit per square at
+/// `rank * 8 + file`. Used as the key of the pawn hash table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct PawnKey {
+    white: u64,
+    black: u64,
+}
+
+impl PawnKey {
+    fn new(board: &Board) -> Self {
+        let mut key = PawnKey::default();
+        for color in [Color::White, Color::Black] {
+            for (piece, pos) in board.colored_pieces(color) {
+                if piece.kind == Kind::Pawn {
+                    *key.get_mut(color) |= 1 << (pos.rank * 8 + pos.file);
+                }
+            }
+        }
+        key
+    }
+
+    fn get(&self, color: Color) -> u64 {
+        match color {
+            Color::White => self.white,
+            Color::Black => self.black,
+        }
+    }
+
+    fn get_mut(&mut self, color: Color) -> &mut u64 {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        (self.white ^ self.black.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+/// The part of the pawn structure that only depends on the pawns, so it
+/// can be cached. Whether a passed pawn is blocked depends on the other
+/// pieces, so the passed pawns are kept aside and scored afterwards.
+#[derive(Debug, Clone, Copy)]
+struct PawnEntry {
+    key: PawnKey,
+    /// The doubled, isolated, backward and connected terms, from the
+    /// point of view of white.
+    score: f32,
+    passed: u64,
+}
+
+/// A fixed size cache of the pawn structure, indexed by the placement of
+/// the pawns. The entries are only valid for the weights they were
+/// computed with, so a table should not be shared between configurations.
+#[derive(Debug, Clone)]
+pub struct PawnHash {
+    entries: Vec<Option<PawnEntry>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl PawnHash {
+    pub fn new(size: usize) -> Self {
+        PawnHash {
+            entries: vec![None; size.max(1)],
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn probe(&mut self, board: &Board, config: &EvalConfig) -> PawnEntry {
+        let key = PawnKey::new(board);
+        let index = (key.hash() % self.entries.len() as u64) as usize;
+        match self.entries[index] {
+            Some(entry) if entry.key == key => {
+                self.hits += 1;
+                entry
+            }
+            _ => {
+                self.misses += 1;
+                let entry = PawnEntry::new(key, config);
+                self.entries[index] = Some(entry);
+                entry
+            }
+        }
+    }
+}
+
+/// The pawn-structure score from the point of view of white. The
+/// structure is looked up in `pawns` when a table is given.
+pub fn pawn_structure(board: &Board, config: &EvalConfig, pawns: Option<&mut PawnHash>) -> f32 {
+    let entry = match pawns {
+        Some(pawns) => pawns.probe(board, config),
+        None => PawnEntry::new(PawnKey::new(board), config),
+    };
+    entry.score + passed(board, entry, Color::White, config)
+        - passed(board, entry, Color::Black, config)
+}
+
+/// The pawn-structure score of one side, without caching.
+pub fn side_structure(board: &Board, color: Color, config: &EvalConfig) -> f32 {
+    let key = PawnKey::new(board);
+    let (score, passed_pawns) = structure(key, color, config);
+    let entry = PawnEntry {
+        key,
+        score,
+        passed: passed_pawns,
+    };
+    score + passed(board, entry, color, config)
+}
+
+fn has(pawns: u64, rank: isize, file: isize) -> bool {
+    (0..8).contains(&rank) && (0..8).contains(&file) && pawns & 1 << (rank * 8 + file) != 0
+}
+
+fn squares(pawns: u64) -> impl Iterator<Item = Position> {
+    (0..64)
+        .filter(move |i| pawns & 1 << i != 0)
+        .map(|i| Position {
+            rank: i / 8,
+            file: i % 8,
+        })
+}
+
+/// Whether `rank` is strictly in front of `from` for a pawn of `color`.
+fn ahead(color: Color, from: isize, rank: isize) -> bool {
+    (rank - from) * color.pawn_dir() > 0
+}
+
+impl PawnEntry {
+    fn new(key: PawnKey, config: &EvalConfig) -> Self {
+        let mut passed = 0;
+        let mut score = 0.0;
+        for color in [Color::White, Color::Black] {
+            let (terms, bits) = structure(key, color, config);
+            passed |= bits;
+            match color {
+                Color::White => score += terms,
+                Color::Black => score -= terms,
+            }
+        }
+        PawnEntry { key, score, passed }
+    }
+}
+
+/// The structure terms of the pawns of `color`, and its passed pawns.
+fn structure(key: PawnKey, color: Color, config: &EvalConfig) -> (f32, u64) {
+    let weights = &config.pawn_structure;
+    let own = key.get(color);
+    let enemy = key.get(color.opposite());
+    let dir = color.pawn_dir();
+    let mut score = 0.0;
+    let mut passed = 0;
+    for file in 0..8 {
+        let count = (0..8)
+            .filter(|&rank| has(own, rank, file))
+            .count();
+        if count > 1 {
+            score += weights.doubled * (count - 1) as f32;
+        }
+    }
+    for Position { rank, file } in squares(own) {
+        let neighbours: Vec<_> = [file - 1, file + 1]
+            .into_iter()
+            .flat_map(|file| (0..8).map(move |rank| (rank, file)))
+            .filter(|&(rank, file)| has(own, rank, file))
+            .collect();
+        let connected = neighbours
+            .iter()
+            .any(|&(r, _)| r == rank || r == rank - dir);
+        if neighbours.is_empty() {
+            score += weights.isolated;
+        } else if connected {
+            score += weights.connected;
+        } else if neighbours
+            .iter()
+            .all(|&(r, _)| ahead(color, rank, r))
+            && (has(enemy, rank + 2 * dir, file - 1) || has(enemy, rank + 2 * dir, file + 1))
+        {
+            // every neighbour is ahead, and the square in front is
+            // controlled by an enemy pawn
+            score += weights.backward;
+        }
+        let is_passed = !(file - 1..=file + 1)
+            .any(|file| (0..8).any(|r| ahead(color, rank, r) && has(enemy, r, file)));
+        if is_passed {
+            passed |= 1 << (rank * 8 + file);
+        }
+    }
+    (score, passed)
+}
+
+/// The bonus of the passed pawns of `color`, growing with the ranks they
+/// have advanced and reduced when they are blocked.
+fn passed(board: &Board, entry: PawnEntry, color: Color, config: &EvalConfig) -> f32 {
+    let weights = &config.pawn_structure;
+    let own = entry.key.get(color) & entry.passed;
+    squares(own)
+        .map(|pos| {
+            let advanced = (pos.rank - color.pawn_start()).abs() + 1;
+            let bonus = weights.passed[advanced.min(7) as usize];
+            let front = Position {
+                rank: pos.rank + color.pawn_dir(),
+                file: pos.file,
+            };
+            if board.get(front).is_some() {
+                bonus * weights.blocked_passed
+            } else {
+                bonus
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+fn terms(fen: &str) -> (f32, u64) {
+    let (board, _) = Board::from_fen(fen).unwrap();
+    structure(PawnKey::new(&board), Color::White, &EvalConfig::default())
+}
+
+#[test]
+fn pawn_terms() {
+    let config = EvalConfig::default();
+    let weights = &config.pawn_structure;
+    // doubled and isolated on the e file
+    let (score, _) = terms("4k3/8/8/8/4P3/8/4P3/4K3 w - - 0 1");
+    assert_eq!(score, weights.doubled + 2.0 * weights.isolated);
+    // a pawn chain, d3 is defended by c2
+    let (score, _) = terms("4k3/8/8/8/8/3P4/2P5/4K3 w - - 0 1");
+    assert_eq!(score, weights.connected);
+    // the c2 pawn is behind d3, and c3 is controlled by b4
+    let (score, passed) = terms("4k3/8/8/8/1p6/3P4/2P5/4K3 w - - 0 1");
+    assert_eq!(score, weights.connected + weights.backward);
+    assert_eq!(passed, 1 << (5 * 8 + 3));
+}
+
+#[test]
+fn passed_pawns() {
+    let config = EvalConfig::default();
+    let weights = &config.pawn_structure;
+    let (board, _) = Board::from_fen("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let score = pawn_structure(&board, &config, None);
+    assert_eq!(score, weights.isolated + weights.passed[5]);
+
+    let (blocked, _) = Board::from_fen("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let score = pawn_structure(&blocked, &config, None);
+    assert_eq!(
+        score,
+        weights.isolated + weights.passed[5] * weights.blocked_passed
+    );
+
+    // the cache only stores the pawns, blocking is checked every time
+    let mut pawns = PawnHash::new(64);
+    assert_eq!(
+        pawn_structure(&board, &config, Some(&mut pawns)),
+        pawn_structure(&board, &config, None)
+    );
+    assert_eq!(pawn_structure(&blocked, &config, Some(&mut pawns)), score);
+    assert_eq!((pawns.hits, pawns.misses), (1, 1));
+}
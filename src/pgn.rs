@@ -0,0 +1,275 @@
+use std::fmt::Display;
+
+use crate::fen::FenError;
+use crate::moves::Move;
+use crate::piece::Color;
+use crate::table::Board;
+
+/// A game in Portable Game Notation. Comments, variations and
+/// numeric annotation glyphs are discarded when parsing.
+#[derive(Debug, Clone, Default)]
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line in standard algebraic notation.
+    pub moves: Vec<String>,
+    pub result: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    Fen(FenError),
+    /// The move at the given ply is not legal or could not be parsed.
+    IllegalMove {
+        ply: usize,
+        san: String,
+    },
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::Fen(err) => write!(f, "invalid FEN tag: {err}"),
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal move {san:?} at ply {ply}"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// One position of a replayed game, together with the move that led to it.
+#[derive(Debug, Clone, Copy)]
+pub struct Ply {
+    pub board: Board,
+    pub turn: Color,
+    pub last_move: Option<Move>,
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+impl Pgn {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The starting position, taken from the `FEN` tag if present.
+    pub fn start(&self) -> Result<(Board, Color), PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen).map_err(PgnError::Fen),
+            None => Ok((Board::default(), Color::White)),
+        }
+    }
+
+    /// Replays the game, returning the starting position followed by the
+    /// position after each ply.
+    pub fn plies(&self) -> Result<Vec<Ply>, PgnError> {
+        let (mut board, mut turn) = self.start()?;
+        let mut plies = vec![Ply {
+            board,
+            turn,
+            last_move: None,
+        }];
+        for (ply, san) in self.moves.iter().enumerate() {
+            let r#move = board
+                .parse_san(san, turn)
+                .ok_or_else(|| PgnError::IllegalMove {
+                    ply: ply + 1,
+                    san: san.clone(),
+                })?;
+            board.apply(r#move);
+            turn = turn.opposite();
+            plies.push(Ply {
+                board,
+                turn,
+                last_move: Some(r#move),
+            });
+        }
+        Ok(plies)
+    }
+}
+
+/// Writes the game in export format, with the movetext wrapped at 80 columns.
+impl Display for Pgn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{name} \"{}\"]", value.replace('"', "\\\""))?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f)?;
+        }
+        let black_starts = matches!(self.start(), Ok((_, Color::Black)));
+        let mut tokens = vec![];
+        for (i, san) in self.moves.iter().enumerate() {
+            let ply = i + black_starts as usize;
+            if ply.is_multiple_of(2) {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            } else if i == 0 {
+                tokens.push("1...".into());
+            }
+            tokens.push(san.clone());
+        }
+        tokens.push(
+            self.result
+                .clone()
+                .unwrap_or_else(|| "*".into()),
+        );
+        let mut width = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 && width + 1 + token.len() > 80 {
+                writeln!(f)?;
+                width = 0;
+            } else if i > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+            write!(f, "{token}")?;
+            width += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// Parses every game in a PGN database.
+pub fn parse(text: &str) -> Vec<Pgn> {
+    let mut games = vec![];
+    let mut game = Pgn::default();
+    let mut in_movetext = false;
+    let mut depth = 0;
+    let mut comment = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('%') {
+            continue;
+        }
+        if !comment && depth == 0 && line.starts_with('[') {
+            if in_movetext {
+                games.push(std::mem::take(&mut game));
+                in_movetext = false;
+            }
+            if let Some(tag) = parse_tag(line) {
+                game.tags.push(tag);
+            }
+            continue;
+        }
+        let mut token = String::new();
+        for c in line.chars().chain([' ']) {
+            if comment {
+                comment = c != '}';
+                continue;
+            }
+            match c {
+                '{' => comment = true,
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if depth > 0 => {}
+                // a semicolon starts a comment until the end of the line
+                c if c.is_whitespace() || c == ';' => {
+                    in_movetext |= !token.is_empty();
+                    push_token(&mut game, std::mem::take(&mut token));
+                    // the result ends the game, even without tags after it
+                    if game.result.is_some() {
+                        games.push(std::mem::take(&mut game));
+                        in_movetext = false;
+                    }
+                    if c == ';' {
+                        break;
+                    }
+                }
+                c => token.push(c),
+            }
+        }
+    }
+    if in_movetext || !game.tags.is_empty() {
+        games.push(game);
+    }
+    games
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line
+        .strip_prefix('[')?
+        .strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+fn push_token(game: &mut Pgn, token: String) {
+    if RESULTS.contains(&token.as_str()) {
+        game.result = Some(token);
+        return;
+    }
+    // move numbers may be glued to the move, as in `1.e4`, but castling
+    // may also be written with zeros, as in `0-0`
+    let number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let token = match number.strip_prefix('.') {
+        Some(rest) if number.len() < token.len() => rest.trim_start_matches('.'),
+        _ => &token,
+    };
+    if !token.is_empty() && !token.starts_with('$') {
+        game.moves
+            .push(token.to_string());
+    }
+}
+
+#[test]
+fn parse_game() {
+    let text = r#"[Event "Test"]
+[Site "?"]
+
+1. e4 e5 2. Nf3 {a comment} Nc6 (2... d6 3. d4) 3. Bb5 a6 $1
+4. O-O 1-0
+
+[Event "Second"]
+
+1.d4 d5 *
+"#;
+    let games = parse(text);
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].tag("Event"), Some("Test"));
+    assert_eq!(
+        games[0].moves,
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]
+    );
+    assert_eq!(games[0].result.as_deref(), Some("1-0"));
+    assert_eq!(games[1].moves, ["d4", "d5"]);
+    assert_eq!(parse("1. e4 e5 1-0 1. d4 *").len(), 2);
+    assert_eq!(parse("1. e4; a comment\ne5 *")[0].moves, ["e4", "e5"]);
+    assert_eq!(
+        parse("10.0-0 0-0-0 11...Kb8 *")[0].moves,
+        ["0-0", "0-0-0", "Kb8"]
+    );
+    assert_eq!(
+        games[0]
+            .plies()
+            .unwrap()
+            .len(),
+        8
+    );
+}
+
+#[test]
+fn write_game() {
+    let game = Pgn {
+        tags: vec![
+            ("Event".into(), "Test".into()),
+            (
+                "FEN".into(),
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".into(),
+            ),
+        ],
+        moves: vec!["e5".into(), "Nf3".into(), "Nc6".into()],
+        result: Some("1/2-1/2".into()),
+    };
+    let text = game.to_string();
+    assert!(text.ends_with("\n1... e5 2. Nf3 Nc6 1/2-1/2\n"));
+    let parsed = parse(&text);
+    assert_eq!(parsed[0].moves, game.moves);
+    assert_eq!(parsed[0].tags, game.tags);
+}
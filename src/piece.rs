@@ -1,12 +1,12 @@
 pub use Color::*;
 pub use Kind::*;
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub kind: Kind,
     pub color: Color,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Kind {
     King,
     Queen,
@@ -15,12 +15,65 @@ pub enum Kind {
     Rook,
     Pawn,
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     Black,
     White,
 }
 
+impl Piece {
+    /// The FEN letter of the piece, uppercase for white and lowercase for black.
+    pub fn symbol(self) -> char {
+        let symbol = match self.kind {
+            King => 'k',
+            Queen => 'q',
+            Bishop => 'b',
+            Knight => 'n',
+            Rook => 'r',
+            Pawn => 'p',
+        };
+        match self.color {
+            White => symbol.to_ascii_uppercase(),
+            Black => symbol,
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Piece> {
+        let kind = match symbol.to_ascii_lowercase() {
+            'k' => King,
+            'q' => Queen,
+            'b' => Bishop,
+            'n' => Knight,
+            'r' => Rook,
+            'p' => Pawn,
+            _ => return None,
+        };
+        let color = if symbol.is_ascii_uppercase() {
+            White
+        } else {
+            Black
+        };
+        Some(Piece { kind, color })
+    }
+
+    pub fn unicode(self) -> char {
+        match (self.kind, self.color) {
+            (Bishop, White) => '♗',
+            (Rook, White) => '♖',
+            (King, White) => '♔',
+            (Queen, White) => '♕',
+            (Pawn, White) => '♙',
+            (Knight, White) => '♘',
+            (Bishop, Black) => '♝',
+            (Rook, Black) => '♜',
+            (King, Black) => '♚',
+            (Queen, Black) => '♛',
+            (Pawn, Black) => '♟',
+            (Knight, Black) => '♞',
+        }
+    }
+}
+
 impl Color {
     pub fn opposite(self) -> Color {
         match self {
@@ -34,6 +87,13 @@ impl Color {
             White => 6,
         }
     }
+    /// The rank index where the pieces of this color start.
+    pub fn back_rank(self) -> isize {
+        match self {
+            Black => 0,
+            White => 7,
+        }
+    }
     pub fn pawn_dir(self) -> isize {
         match self {
             Black => 1,
@@ -0,0 +1,294 @@
+use std::fmt::Display;
+
+use crate::moves::{Move, Position};
+use crate::piece::{Color, Kind};
+use crate::table::Board;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Chess glyphs and box-drawing characters.
+    Unicode,
+    /// FEN letters and `+-|` borders, for terminals without unicode support.
+    Ascii,
+}
+
+/// Extra information shown to the right of the board.
+#[derive(Debug, Clone, Copy)]
+pub struct Panel {
+    pub turn: Color,
+    pub evaluation: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    /// The side whose pieces are drawn at the bottom of the board.
+    pub orientation: Color,
+    pub charset: Charset,
+    /// Paint the squares with ANSI background colors.
+    pub colored: bool,
+    /// The squares of this move are highlighted.
+    pub last_move: Option<Move>,
+    /// Highlight the king of a side that is in check.
+    pub check: bool,
+    pub panel: Option<Panel>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            orientation: Color::White,
+            charset: Charset::Unicode,
+            colored: false,
+            last_move: None,
+            check: false,
+            panel: None,
+        }
+    }
+}
+
+/// A board ready to be displayed with some rendering options.
+pub struct Render<'a> {
+    board: &'a Board,
+    options: RenderOptions,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Highlight {
+    None,
+    LastMove,
+    Check,
+}
+
+const LIGHT: u8 = 223;
+const DARK: u8 = 137;
+const LAST_MOVE: u8 = 186;
+const CHECK: u8 = 160;
+
+struct Borders {
+    top: [&'static str; 3],
+    middle: [&'static str; 3],
+    bottom: [&'static str; 3],
+    horizontal: &'static str,
+    vertical: &'static str,
+}
+
+const UNICODE: Borders = Borders {
+    top: ["╔", "╦", "╗"],
+    middle: ["╠", "╬", "╣"],
+    bottom: ["╚", "╩", "╝"],
+    horizontal: "═══",
+    vertical: "║",
+};
+
+const ASCII: Borders = Borders {
+    top: ["+", "+", "+"],
+    middle: ["+", "+", "+"],
+    bottom: ["+", "+", "+"],
+    horizontal: "---",
+    vertical: "|",
+};
+
+impl Board {
+    pub fn render(&self, options: RenderOptions) -> Render<'_> {
+        Render {
+            board: self,
+            options,
+        }
+    }
+}
+
+impl Render<'_> {
+    fn highlight(&self, pos: Position) -> Highlight {
+        let options = &self.options;
+        let checked = options.check
+            && self
+                .board
+                .get(pos)
+                .filter(|piece| piece.kind == Kind::King)
+                .map(|piece| {
+                    self.board
+                        .in_check(piece.color)
+                })
+                .unwrap_or(false);
+        if checked {
+            Highlight::Check
+        } else if options
+            .last_move
+            .map(|m| m.from == pos || m.to == pos)
+            .unwrap_or(false)
+        {
+            Highlight::LastMove
+        } else {
+            Highlight::None
+        }
+    }
+
+    fn square(&self, pos: Position) -> String {
+        let glyph = match (self.board[pos], self.options.charset) {
+            (Some(piece), Charset::Unicode) => piece.unicode(),
+            (Some(piece), Charset::Ascii) => piece.symbol(),
+            (None, _) => ' ',
+        };
+        let highlight = self.highlight(pos);
+        if self.options.colored {
+            let background = match highlight {
+                Highlight::Check => CHECK,
+                Highlight::LastMove => LAST_MOVE,
+                Highlight::None if (pos.rank + pos.file) % 2 == 0 => LIGHT,
+                Highlight::None => DARK,
+            };
+            format!("\x1b[48;5;{background}m\x1b[38;5;16m {glyph} \x1b[0m")
+        } else {
+            match highlight {
+                Highlight::Check => format!("*{glyph}*"),
+                Highlight::LastMove => format!("[{glyph}]"),
+                Highlight::None => format!(" {glyph} "),
+            }
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let borders = match self.options.charset {
+            Charset::Unicode => &UNICODE,
+            Charset::Ascii => &ASCII,
+        };
+        let flipped = self.options.orientation == Color::Black;
+        let order = |i: isize| if flipped { 7 - i } else { i };
+        let rule = |[left, middle, right]: [&str; 3]| {
+            // colored squares are not separated by borders, so neither are
+            // the horizontals above and below them
+            let middle = if self.options.colored { "" } else { middle };
+            format!("  {left}{}{right}", [borders.horizontal; 8].join(middle))
+        };
+
+        let mut lines = vec![];
+        for row in 0..8 {
+            let rank = order(row);
+            if row == 0 {
+                lines.push(rule(borders.top));
+            } else if !self.options.colored {
+                lines.push(rule(borders.middle));
+            }
+            let mut line = format!("{} {}", 8 - rank, borders.vertical);
+            for column in 0..8 {
+                let file = order(column);
+                line.push_str(&self.square(Position { rank, file }));
+                if !self.options.colored || column == 7 {
+                    line.push_str(borders.vertical);
+                }
+            }
+            lines.push(line);
+        }
+        lines.push(rule(borders.bottom));
+        // colored squares are not separated by borders
+        let width = if self.options.colored { 3 } else { 4 };
+        let files: String = (0..8)
+            .map(|column| (b'A' + order(column) as u8) as char)
+            .map(|file| format!("{file:^width$}"))
+            .collect();
+        lines.push(format!("   {}", files.trim_end()));
+        lines
+    }
+
+    fn panel(&self) -> Vec<String> {
+        let Some(panel) = self.options.panel else {
+            return vec![];
+        };
+        let mut lines = vec![
+            format!("FEN: {}", self.board.fen(panel.turn)),
+            format!("Side to move: {:?}", panel.turn),
+        ];
+        if let Some(evaluation) = panel.evaluation {
+            lines.push(format!("Evaluation: {evaluation:+.2}"));
+        }
+        lines
+    }
+}
+
+impl Display for Render<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let panel = self.panel();
+        for (i, line) in self
+            .lines()
+            .into_iter()
+            .enumerate()
+        {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{line}")?;
+            // the panel starts next to the first rank row
+            if let Some(extra) = i
+                .checked_sub(1)
+                .and_then(|i| panel.get(i))
+            {
+                write!(f, "   {extra}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.render(RenderOptions::default())
+            .fmt(f)
+    }
+}
+
+#[test]
+fn rank_eight_on_top() {
+    let board = Board::default().to_string();
+    let lines: Vec<_> = board.lines().collect();
+    assert!(lines[1].starts_with("8 ║ ♜"));
+    assert!(lines[15].starts_with("1 ║ ♖"));
+    assert!(lines[17].starts_with("    A   B"));
+}
+
+#[test]
+fn flipped_ascii() {
+    let options = RenderOptions {
+        orientation: Color::Black,
+        charset: Charset::Ascii,
+        ..Default::default()
+    };
+    let board = Board::default()
+        .render(options)
+        .to_string();
+    let lines: Vec<_> = board.lines().collect();
+    assert_eq!(lines[1], "1 | R | N | B | K | Q | B | N | R |");
+    assert_eq!(lines[15], "8 | r | n | b | k | q | b | n | r |");
+    assert!(lines[17].starts_with("    H   G"));
+}
+
+#[test]
+fn colored_lines_line_up() {
+    let options = RenderOptions {
+        colored: true,
+        ..Default::default()
+    };
+    let board = Board::default()
+        .render(options)
+        .to_string();
+    // the width on screen, without the ANSI color codes
+    let width = |line: &str| {
+        line.split('\x1b')
+            .enumerate()
+            .map(|(i, part)| {
+                if i == 0 {
+                    part
+                } else {
+                    &part[part.find('m').unwrap() + 1..]
+                }
+            })
+            .map(|part| part.chars().count())
+            .sum::<usize>()
+    };
+    let lines: Vec<_> = board.lines().collect();
+    let (files, board) = lines.split_last().unwrap();
+    assert_eq!(board.len(), 10);
+    assert!(board
+        .iter()
+        .all(|line| width(line) == width(board[0])));
+    assert!(width(files) <= width(board[0]));
+}
@@ -0,0 +1,38 @@
+use std::str::FromStr;
+
+use crate::mcts;
+use crate::minimax::{self, Limits, SearchResult};
+use crate::piece::Color;
+use crate::settings::EvalConfig;
+use crate::table::Board;
+
+/// The algorithm that searches for moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Searcher {
+    /// Iterative deepening alpha-beta, see [`minimax::search`].
+    #[default]
+    Minimax,
+    /// Monte Carlo tree search, see [`mcts::search`].
+    Mcts,
+}
+
+impl FromStr for Searcher {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimax" => Ok(Searcher::Minimax),
+            "mcts" => Ok(Searcher::Mcts),
+            _ => Err(format!(
+                "unknown search {s:?}, expected `minimax` or `mcts`"
+            )),
+        }
+    }
+}
+
+/// Searches the position with the [`Searcher`] of the limits.
+pub fn search(board: Board, turn: Color, limits: Limits, config: &EvalConfig) -> SearchResult {
+    match limits.searcher {
+        Searcher::Minimax => minimax::search(board, turn, limits, config),
+        Searcher::Mcts => mcts::search(board, turn, &limits, config),
+    }
+}
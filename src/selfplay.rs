@@ -0,0 +1,422 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::clock::{Clock, TimeControl};
+use crate::game::{Game, Outcome};
+use crate::minimax::Limits;
+use crate::pgn::{Pgn, PgnError};
+use crate::piece::Color;
+use crate::search::search;
+use crate::settings::EvalConfig;
+use crate::table::Board;
+
+/// One side of a match.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub name: String,
+    pub config: EvalConfig,
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchOptions {
+    /// The number of games, each opening is played twice with the colors
+    /// swapped so the number is rounded up to an even one.
+    pub games: usize,
+    pub threads: usize,
+    /// Games longer than this are adjudicated as draws.
+    pub max_plies: usize,
+    /// The match stops early once the test reaches a verdict.
+    pub sprt: Option<Sprt>,
+    /// The clock of each player, who otherwise think for as long as
+    /// their limits allow.
+    pub time_control: Option<TimeControl>,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            games: 100,
+            threads: 1,
+            max_plies: 400,
+            sprt: None,
+            time_control: None,
+        }
+    }
+}
+
+/// The games won, drawn and lost by the first player.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// The parameters of a sequential probability ratio test between the
+/// hypothesis that the first player is `elo0` stronger (H0) and that it
+/// is `elo1` stronger (H1).
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The first player is not better by `elo1`.
+    H0,
+    /// The first player is better by at least `elo0`.
+    H1,
+    Inconclusive,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::H0 => write!(f, "H0 accepted"),
+            Verdict::H1 => write!(f, "H1 accepted"),
+            Verdict::Inconclusive => write!(f, "inconclusive"),
+        }
+    }
+}
+
+/// The expected score of a player `elo` points stronger than its opponent.
+fn expected(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The points scored per game.
+    pub fn ratio(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// The variance of the result of a single game.
+    fn variance(&self) -> f64 {
+        let games = self.games().max(1) as f64;
+        let score = self.ratio();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+
+    /// The Elo difference and the margin of its 95% confidence interval,
+    /// `None` while all the games have the same result.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.ratio();
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        if self.games() == 0 || deviation == 0.0 {
+            return None;
+        }
+        let low = (score - 1.96 * deviation).max(1e-6);
+        let high = (score + 1.96 * deviation).min(1.0 - 1e-6);
+        Some((elo(score), (elo(high) - elo(low)) / 2.0))
+    }
+
+    /// The log likelihood ratio of H1 against H0, approximated with the
+    /// normal distribution of the game results.
+    pub fn llr(&self, sprt: Sprt) -> f64 {
+        let variance = self.variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected(sprt.elo0), expected(sprt.elo1));
+        let games = self.games() as f64;
+        games * (s1 - s0) * (2.0 * self.ratio() - s0 - s1) / (2.0 * variance)
+    }
+
+    /// The bounds of the log likelihood ratio for accepting H0 and H1.
+    pub fn bounds(sprt: Sprt) -> (f64, f64) {
+        (
+            (sprt.beta / (1.0 - sprt.alpha)).ln(),
+            ((1.0 - sprt.beta) / sprt.alpha).ln(),
+        )
+    }
+
+    pub fn verdict(&self, sprt: Sprt) -> Verdict {
+        let llr = self.llr(sprt);
+        let (lower, upper) = Score::bounds(sprt);
+        if llr >= upper {
+            Verdict::H1
+        } else if llr <= lower {
+            Verdict::H0
+        } else {
+            Verdict::Inconclusive
+        }
+    }
+}
+
+/// Reads an opening set, either a PGN database whose moves are played
+/// before the engines take over, or one FEN or EPD position per line.
+pub fn parse_openings(text: &str) -> Result<Vec<Pgn>, PgnError> {
+    let fen = |line: &str| {
+        line.split_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+    let is_fen = lines
+        .peek()
+        .is_some_and(|line| Board::from_fen(&fen(line)).is_ok());
+    if !is_fen {
+        return Ok(crate::pgn::parse(text));
+    }
+    lines
+        .map(|line| {
+            let fen = fen(line);
+            Board::from_fen(&fen).map_err(PgnError::Fen)?;
+            Ok(Pgn {
+                tags: vec![("SetUp".into(), "1".into()), ("FEN".into(), fen)],
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Plays a game from the end of the opening, returning it with the
+/// opening moves included.
+pub fn play_game(
+    opening: &Pgn,
+    white: &Player,
+    black: &Player,
+    options: &MatchOptions,
+) -> Result<Pgn, PgnError> {
+    let mut game = Game::from_pgn(opening)?;
+    let mut clocks = options
+        .time_control
+        .map(|control| [Clock::new(control); 2]);
+    let mut pgn = Pgn {
+        tags: vec![
+            ("Event".into(), "Self-play match".into()),
+            ("White".into(), white.name.clone()),
+            ("Black".into(), black.name.clone()),
+        ],
+        ..Default::default()
+    };
+    pgn.tags.extend(
+        ["SetUp", "FEN"]
+            .iter()
+            .filter_map(|&tag| Some((tag.to_string(), opening.tag(tag)?.to_string()))),
+    );
+    let result = loop {
+        if let Some(outcome) = game.result() {
+            break outcome.result();
+        }
+        if game.moves().len() >= options.max_plies {
+            break "1/2-1/2";
+        }
+        let (player, side) = match game.turn() {
+            Color::White => (white, 0),
+            Color::Black => (black, 1),
+        };
+        let mut limits = player.limits.clone();
+        limits.clock = clocks.map(|clocks| clocks[side].allocate());
+        let start = Instant::now();
+        let Some(r#move) = search(*game.board(), game.turn(), limits, &player.config).best_move
+        else {
+            break "*";
+        };
+        if let Some(clock) = clocks
+            .as_mut()
+            .map(|clocks| &mut clocks[side])
+        {
+            clock.punch(start.elapsed());
+            if clock.flagged() {
+                let winner = game.turn().opposite();
+                break Outcome::Timeout { winner }.result();
+            }
+        }
+        game.push(r#move);
+    };
+    pgn.moves = game.san();
+    pgn.result = Some(result.into());
+    Ok(pgn)
+}
+
+/// Plays a match between `first` and `second` over the openings, each
+/// one twice with the colors swapped, in parallel threads. `on_game` is
+/// called after every game with the game and the score so far.
+pub fn play_match(
+    first: &Player,
+    second: &Player,
+    openings: &[Pgn],
+    options: &MatchOptions,
+    on_game: impl Fn(&Pgn, Score) + Sync,
+) -> Result<(Score, Vec<Pgn>), PgnError> {
+    let default = [Pgn::default()];
+    let openings = if openings.is_empty() {
+        &default[..]
+    } else {
+        openings
+    };
+    let games = options.games.div_ceil(2) * 2;
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let results = Mutex::new((Score::default(), vec![], None));
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| loop {
+                let round = next.fetch_add(1, Ordering::Relaxed);
+                if round >= games || stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let opening = &openings[round / 2 % openings.len()];
+                // the first player has white in the even rounds
+                let (white, black) = if round.is_multiple_of(2) {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                let game = play_game(opening, white, black, options).map(|mut game| {
+                    game.tags
+                        .insert(1, ("Round".into(), (round + 1).to_string()));
+                    game
+                });
+                let mut results = results.lock().unwrap();
+                let (score, finished, error) = &mut *results;
+                let game = match game {
+                    Ok(game) => game,
+                    Err(err) => {
+                        *error = Some(err);
+                        stop.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                };
+                let white_points = match game.result.as_deref() {
+                    Some("1-0") => 1,
+                    Some("0-1") => -1,
+                    _ => 0,
+                };
+                let points = if round.is_multiple_of(2) {
+                    white_points
+                } else {
+                    -white_points
+                };
+                match points {
+                    1 => score.wins += 1,
+                    -1 => score.losses += 1,
+                    _ => score.draws += 1,
+                }
+                on_game(&game, *score);
+                if let Some(sprt) = options.sprt {
+                    if score.verdict(sprt) != Verdict::Inconclusive {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+                finished.push((round, game));
+            });
+        }
+    });
+    let (score, mut finished, error) = results.into_inner().unwrap();
+    if let Some(err) = error {
+        return Err(err);
+    }
+    finished.sort_by_key(|(round, _)| *round);
+    Ok((
+        score,
+        finished
+            .into_iter()
+            .map(|(_, game)| game)
+            .collect(),
+    ))
+}
+
+#[test]
+fn elo_and_sprt() {
+    let even = Score {
+        wins: 10,
+        draws: 20,
+        losses: 10,
+    };
+    let (elo, margin) = even.elo().unwrap();
+    assert!(elo.abs() < 1e-9 && margin > 0.0);
+    let winning = Score {
+        wins: 300,
+        draws: 100,
+        losses: 100,
+    };
+    assert!(winning.elo().unwrap().0 > 100.0);
+    assert_eq!(winning.verdict(Sprt::default()), Verdict::H1);
+    let losing = Score {
+        wins: 100,
+        draws: 100,
+        losses: 300,
+    };
+    assert_eq!(losing.verdict(Sprt::default()), Verdict::H0);
+    assert_eq!(Score::default().elo(), None);
+}
+
+#[test]
+fn opening_formats() {
+    let pgn = parse_openings("1. e4 e5 *\n\n1. d4 d5 *\n").unwrap();
+    assert_eq!(pgn.len(), 2);
+    assert_eq!(pgn[1].moves, ["d4", "d5"]);
+    let fens = parse_openings(
+        "# two positions\n4k3/8/8/8/8/8/8/4K2R w K - c0 \"test\";\n4k3/8/8/8/8/8/8/4K2R b K -\n",
+    )
+    .unwrap();
+    assert_eq!(fens.len(), 2);
+    assert_eq!(fens[0].tag("FEN"), Some("4k3/8/8/8/8/8/8/4K2R w K -"));
+}
+
+#[test]
+fn short_match() {
+    let player = |name: &str| Player {
+        name: name.into(),
+        config: EvalConfig::default(),
+        limits: Limits {
+            depth: 2,
+            ..Default::default()
+        },
+    };
+    // a mate in one for white, so white always wins
+    let openings = parse_openings("6k1/5ppp/8/8/8/8/8/R5K1 w - -").unwrap();
+    let options = MatchOptions {
+        games: 2,
+        threads: 2,
+        ..Default::default()
+    };
+    let (score, games) =
+        play_match(&player("a"), &player("b"), &openings, &options, |_, _| ()).unwrap();
+    assert_eq!(
+        score,
+        Score {
+            wins: 1,
+            draws: 0,
+            losses: 1
+        }
+    );
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[1].tag("White"), Some("b"));
+    assert_eq!(games[0].moves, ["Ra8#"]);
+}
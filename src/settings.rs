@@ -2,29 +2,682 @@ use crate::{
     moves::Position,
     piece::{Color, Kind, Piece},
 };
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt::Display;
 use std::ops::Index;
-use tap::prelude::*;
+use std::path::Path;
 
 use Kind::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The settings file the engine ships with, used when no other is given.
+pub const DEFAULT_SETTINGS: &str = include_str!("../settings.json");
+
+/// The value of a piece on each square, indexed by rank and file, for each
+/// color. In the settings file only one of the colors needs to be given,
+/// the other one is mirrored from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SidedTable")]
 pub struct ValueTable {
-    white: [[f32; 8]; 8],
-    black: [[f32; 8]; 8],
+    pub white: [[f32; 8]; 8],
+    pub black: [[f32; 8]; 8],
+}
+
+#[derive(Deserialize)]
+struct SidedTable {
+    white: Option<[[f32; 8]; 8]>,
+    black: Option<[[f32; 8]; 8]>,
+}
+
+impl TryFrom<SidedTable> for ValueTable {
+    type Error = &'static str;
+    fn try_from(table: SidedTable) -> Result<Self, Self::Error> {
+        match (table.white, table.black) {
+            (Some(white), Some(black)) => Ok(ValueTable { white, black }),
+            (Some(white), None) => Ok(ValueTable::mirrored(white)),
+            (None, Some(black)) => {
+                let mirror = ValueTable::mirrored(black);
+                Ok(ValueTable {
+                    white: mirror.black,
+                    black: mirror.white,
+                })
+            }
+            (None, None) => Err("expected a `white` or `black` table"),
+        }
+    }
+}
+
+impl ValueTable {
+    /// Builds the table of both colors from the white one, flipping the ranks.
+    pub fn mirrored(white: [[f32; 8]; 8]) -> Self {
+        let mut black = white;
+        black.reverse();
+        ValueTable { white, black }
+    }
+}
+
+/// The weights of the evaluation function, usually read from `settings.json`.
+/// Settings files older than the pawn-structure, king-safety and activity
+/// weights use the defaults of these sections, which are those of
+/// `settings.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalConfig {
+    pub kingside_castle: f32,
+    pub queenside_castle: f32,
+    #[serde(default)]
+    pub pawn_structure: PawnWeights,
+    #[serde(default)]
+    pub king_safety: KingSafety,
+    #[serde(default)]
+    pub activity: Activity,
+    pub pawn: PieceTables,
+    pub knight: PieceTables,
+    pub queen: PieceTables,
+    pub king: PieceTables,
+    pub bishop: PieceTables,
+    pub rook: PieceTables,
+}
+
+/// The weights of the pawn-structure terms. Penalties are negative.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PawnWeights {
+    /// For each pawn on a file beyond the first one.
+    pub doubled: f32,
+    /// For a pawn without friendly pawns on the adjacent files.
+    pub isolated: f32,
+    /// For a pawn behind its neighbours that cannot advance safely.
+    pub backward: f32,
+    /// For a pawn defended by, or side by side with, another pawn.
+    pub connected: f32,
+    /// For a passed pawn, indexed by its rank counted from its own side,
+    /// so a pawn on its starting square uses the second entry.
+    pub passed: [f32; 8],
+    /// Scales the passed pawn bonus when the square in front is occupied.
+    pub blocked_passed: f32,
+}
+
+impl Default for PawnWeights {
+    fn default() -> Self {
+        PawnWeights {
+            doubled: -0.15,
+            isolated: -0.15,
+            backward: -0.1,
+            connected: 0.05,
+            passed: [0.0, 0.05, 0.1, 0.2, 0.35, 0.6, 1.0, 0.0],
+            blocked_passed: 0.5,
+        }
+    }
+}
+
+/// The weights of the king-safety terms, applied to each king only when
+/// the opponent has enough material left to attack it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KingSafety {
+    /// For each pawn of the king's side right in front of it, on its file
+    /// or on the adjacent ones.
+    pub shield: f32,
+    /// For each shield pawn two squares in front of the king.
+    pub shield_far: f32,
+    /// For each enemy pawn on those files less than four ranks away.
+    pub storm: f32,
+    /// For each file around the king without pawns.
+    pub open_file: f32,
+    /// For each file around the king with only enemy pawns.
+    pub half_open_file: f32,
+    /// The material the opponent needs for the king safety to count,
+    /// with minor pieces worth 1, rooks 2 and queens 4.
+    pub min_material: f32,
+    /// The penalty for each square next to the king attacked by a piece.
+    pub attacks: PieceWeights,
+}
+
+impl Default for KingSafety {
+    fn default() -> Self {
+        KingSafety {
+            shield: 0.15,
+            shield_far: 0.07,
+            storm: -0.1,
+            open_file: -0.25,
+            half_open_file: -0.1,
+            min_material: 5.0,
+            attacks: PieceWeights {
+                knight: 0.05,
+                bishop: 0.05,
+                rook: 0.1,
+                queen: 0.15,
+            },
+        }
+    }
+}
+
+/// The weights of the piece-activity terms.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Activity {
+    /// For each square a piece can move to that is not attacked by an
+    /// enemy pawn.
+    pub mobility: PieceWeights,
+    /// For a rook on a file without pawns.
+    pub rook_open_file: f32,
+    /// For a rook on a file with only enemy pawns.
+    pub rook_half_open_file: f32,
+    pub bishop_pair: f32,
+    /// For a knight in the enemy half, defended by a pawn, that no enemy
+    /// pawn can chase away.
+    pub knight_outpost: f32,
+    /// For each piece, other than the king, defended by another piece.
+    pub defended: f32,
+    /// For each enemy piece attacked.
+    pub attacked: f32,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Activity {
+            mobility: PieceWeights {
+                knight: 0.04,
+                bishop: 0.04,
+                rook: 0.02,
+                queen: 0.01,
+            },
+            rook_open_file: 0.2,
+            rook_half_open_file: 0.1,
+            bishop_pair: 0.3,
+            knight_outpost: 0.25,
+            defended: 0.03,
+            attacked: 0.05,
+        }
+    }
+}
+
+/// A weight for each piece other than pawns and kings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceWeights {
+    pub knight: f32,
+    pub bishop: f32,
+    pub rook: f32,
+    pub queen: f32,
+}
+
+/// The tables of a piece for the midgame and for the endgame. In the
+/// settings file a piece may have a single table, used in both phases.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PhasedTables")]
+pub struct PieceTables {
+    pub midgame: ValueTable,
+    pub endgame: ValueTable,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PhasedTables {
+    Tapered {
+        midgame: Box<ValueTable>,
+        endgame: Box<ValueTable>,
+    },
+    Single(Box<ValueTable>),
+}
+
+impl From<PhasedTables> for PieceTables {
+    fn from(tables: PhasedTables) -> Self {
+        match tables {
+            PhasedTables::Tapered { midgame, endgame } => PieceTables {
+                midgame: *midgame,
+                endgame: *endgame,
+            },
+            PhasedTables::Single(table) => PieceTables {
+                midgame: (*table).clone(),
+                endgame: *table,
+            },
+        }
+    }
+}
+
+const SCALARS: [&str; 2] = ["kingside_castle", "queenside_castle"];
+
+const PAWN_STRUCTURE: [&str; 5] = [
+    "doubled",
+    "isolated",
+    "backward",
+    "connected",
+    "blocked_passed",
+];
+
+const KING_SAFETY: [&str; 6] = [
+    "shield",
+    "shield_far",
+    "storm",
+    "open_file",
+    "half_open_file",
+    "min_material",
+];
+
+const ACTIVITY: [&str; 6] = [
+    "rook_open_file",
+    "rook_half_open_file",
+    "bishop_pair",
+    "knight_outpost",
+    "defended",
+    "attacked",
+];
+
+/// The weights of the heuristic replaced by the activity terms, which
+/// older settings files still have.
+const OBSOLETE: [&str; 3] = ["defended_value", "attacked_value", "available_moves"];
+
+const PIECE_WEIGHTS: [&str; 4] = ["knight", "bishop", "rook", "queen"];
+
+const TABLES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+/// A problem found in the settings file, located by its JSON path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(Vec<Problem>),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Io(err) => write!(f, "could not read the settings file: {err}"),
+            SettingsError::Json(err) => write!(f, "invalid settings: {err}"),
+            SettingsError::Invalid(problems) => {
+                write!(f, "the settings file has {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
-pub fn value(tuple: (Piece, Position)) -> f32 {
-    let index = (tuple.1, tuple.0.color);
-    (match tuple.0.kind {
-        Bishop => &BISHOP,
-        Rook => &ROOK,
-        King => &KING,
-        Knight => &KNIGHT,
-        Queen => &QUEEN,
-        Pawn => &PAWN,
-    })[index]
+
+impl std::error::Error for SettingsError {}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        EvalConfig::from_json(DEFAULT_SETTINGS).expect("the default settings are valid")
+    }
+}
+
+impl EvalConfig {
+    /// Parses the settings, validating them first so every problem is
+    /// reported at once instead of only the first one.
+    pub fn from_json(json: &str) -> Result<Self, SettingsError> {
+        EvalConfig::parse(json).map(|(config, _)| config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        EvalConfig::read(path).map(|(config, _)| config)
+    }
+
+    /// Parses the settings like [`EvalConfig::from_json`], also returning
+    /// the obsolete weights that were ignored, so they can be reported.
+    pub fn parse(json: &str) -> Result<(Self, Vec<Problem>), SettingsError> {
+        let value: Value = serde_json::from_str(json).map_err(SettingsError::Json)?;
+        let problems = validate(&value);
+        if !problems.is_empty() {
+            return Err(SettingsError::Invalid(problems));
+        }
+        let ignored = obsolete(&value);
+        let config = serde_json::from_value(value).map_err(SettingsError::Json)?;
+        Ok((config, ignored))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<(Self, Vec<Problem>), SettingsError> {
+        let json = std::fs::read_to_string(path).map_err(SettingsError::Io)?;
+        EvalConfig::parse(&json)
+    }
+
+    /// Writes the settings in the format of `settings.json`, with one rank
+    /// per line and only the white tables, unless the black one is not
+    /// their mirror.
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("the settings are serializable");
+        strip_black(&mut value);
+        let mut json = String::new();
+        write_json(&mut json, &value, 0);
+        json.push('\n');
+        json
+    }
+
+    pub fn table(&self, kind: Kind) -> &PieceTables {
+        match kind {
+            Bishop => &self.bishop,
+            Rook => &self.rook,
+            King => &self.king,
+            Knight => &self.knight,
+            Queen => &self.queen,
+            Pawn => &self.pawn,
+        }
+    }
+
+    /// The value of a piece standing on a square, interpolated between the
+    /// midgame and endgame tables by the game `phase`, which goes from 1 in
+    /// the opening to 0 when only kings and pawns remain.
+    pub fn value(&self, tuple: (Piece, Position), phase: f32) -> f32 {
+        let index = (tuple.1, tuple.0.color);
+        let tables = self.table(tuple.0.kind);
+        tables.midgame[index] * phase + tables.endgame[index] * (1.0 - phase)
+    }
+}
+
+/// Checks that all scalar weights are present and finite, that the
+/// pawn-structure, king-safety and activity weights are complete when
+/// given, and that every piece has an 8x8 table for each color, either
+/// one for the whole game or one for the midgame and one for the endgame.
+pub fn validate(settings: &Value) -> Vec<Problem> {
+    let mut problems = vec![];
+    let Some(root) = settings.as_object() else {
+        problem(
+            &mut problems,
+            "$",
+            format!("expected an object, found {}", kind(settings)),
+        );
+        return problems;
+    };
+    validate_scalars(&mut problems, "$", root, &SCALARS);
+    if let Some(weights) = validate_optional(&mut problems, root, "pawn_structure", &PAWN_STRUCTURE)
+    {
+        validate_passed(&mut problems, weights);
+    }
+    if let Some(weights) = validate_optional(&mut problems, root, "king_safety", &KING_SAFETY) {
+        validate_section(
+            &mut problems,
+            "$.king_safety",
+            weights,
+            "attacks",
+            &PIECE_WEIGHTS,
+        );
+    }
+    if let Some(weights) = validate_optional(&mut problems, root, "activity", &ACTIVITY) {
+        validate_section(
+            &mut problems,
+            "$.activity",
+            weights,
+            "mobility",
+            &PIECE_WEIGHTS,
+        );
+    }
+    for key in TABLES {
+        let path = format!("$.{key}");
+        match root.get(key) {
+            None => problem(&mut problems, &path, "missing piece table".into()),
+            Some(tables) => validate_piece(&mut problems, &path, tables),
+        }
+    }
+    problems
+}
+
+/// The weights of older settings files that are accepted but ignored.
+pub fn obsolete(settings: &Value) -> Vec<Problem> {
+    OBSOLETE
+        .iter()
+        .filter(|key| settings.get(**key).is_some())
+        .map(|key| Problem {
+            path: format!("$.{key}"),
+            message: "no longer used, replaced by the activity weights".into(),
+        })
+        .collect()
+}
+
+fn problem(problems: &mut Vec<Problem>, path: &str, message: String) {
+    problems.push(Problem {
+        path: path.to_string(),
+        message,
+    });
+}
+
+fn validate_scalars(
+    problems: &mut Vec<Problem>,
+    path: &str,
+    object: &serde_json::Map<String, Value>,
+    keys: &[&str],
+) {
+    for key in keys {
+        let path = format!("{path}.{key}");
+        match object.get(*key) {
+            None => problem(problems, &path, "missing weight".into()),
+            Some(value) => {
+                if let Err(message) = finite(value) {
+                    problem(problems, &path, message);
+                }
+            }
+        }
+    }
+}
+
+/// Checks that `key` is an object with the given scalar weights, and
+/// returns it so the rest of its fields can be checked.
+fn validate_section<'a>(
+    problems: &mut Vec<Problem>,
+    parent: &str,
+    object: &'a serde_json::Map<String, Value>,
+    key: &str,
+    scalars: &[&str],
+) -> Option<&'a serde_json::Map<String, Value>> {
+    let path = format!("{parent}.{key}");
+    let Some(weights) = object.get(key) else {
+        problem(problems, &path, "missing weights".into());
+        return None;
+    };
+    let Some(section) = weights.as_object() else {
+        let message = format!("expected an object, found {}", kind(weights));
+        problem(problems, &path, message);
+        return None;
+    };
+    validate_scalars(problems, &path, section, scalars);
+    Some(section)
+}
+
+/// Like [`validate_section`] for the sections of the root that older
+/// settings files do not have, which then use the default weights.
+fn validate_optional<'a>(
+    problems: &mut Vec<Problem>,
+    root: &'a serde_json::Map<String, Value>,
+    key: &str,
+    scalars: &[&str],
+) -> Option<&'a serde_json::Map<String, Value>> {
+    match root.contains_key(key) {
+        true => validate_section(problems, "$", root, key, scalars),
+        false => None,
+    }
+}
+
+fn validate_passed(problems: &mut Vec<Problem>, weights: &serde_json::Map<String, Value>) {
+    let path = "$.pawn_structure.passed";
+    let Some(passed) = weights.get("passed") else {
+        return problem(problems, path, "missing weight".into());
+    };
+    let Some(ranks) = passed.as_array() else {
+        let message = format!("expected an array of 8 ranks, found {}", kind(passed));
+        return problem(problems, path, message);
+    };
+    if ranks.len() != 8 {
+        problem(
+            problems,
+            path,
+            format!("expected 8 ranks, found {}", ranks.len()),
+        );
+    }
+    for (r, rank) in ranks.iter().enumerate() {
+        if let Err(message) = finite(rank) {
+            problem(problems, &format!("{path}[{r}]"), message);
+        }
+    }
+}
+
+fn validate_piece(problems: &mut Vec<Problem>, path: &str, tables: &Value) {
+    let Some(object) = tables.as_object() else {
+        let message = format!(
+            "expected an object with `white` and `black`, found {}",
+            kind(tables)
+        );
+        return problem(problems, path, message);
+    };
+    if !object.contains_key("midgame") && !object.contains_key("endgame") {
+        return validate_table(problems, path, tables);
+    }
+    for phase in ["midgame", "endgame"] {
+        let path = format!("{path}.{phase}");
+        match object.get(phase) {
+            None => problem(problems, &path, "missing table".into()),
+            Some(table) => validate_table(problems, &path, table),
+        }
+    }
+}
+
+fn validate_table(problems: &mut Vec<Problem>, path: &str, table: &Value) {
+    let Some(table) = table.as_object() else {
+        let message = format!(
+            "expected an object with `white` and `black`, found {}",
+            kind(table)
+        );
+        return problem(problems, path, message);
+    };
+    if !table.contains_key("white") && !table.contains_key("black") {
+        let message = "missing table, expected `white`, `black` or both".into();
+        return problem(problems, path, message);
+    }
+    for color in ["white", "black"] {
+        let path = format!("{path}.{color}");
+        // a missing color is mirrored from the other one
+        let Some(rows) = table.get(color) else {
+            continue;
+        };
+        let Some(rows) = rows.as_array() else {
+            let message = format!("expected an array of 8 ranks, found {}", kind(rows));
+            problem(problems, &path, message);
+            continue;
+        };
+        if rows.len() != 8 {
+            problem(
+                problems,
+                &path,
+                format!("expected 8 ranks, found {}", rows.len()),
+            );
+        }
+        for (r, row) in rows.iter().enumerate() {
+            let path = format!("{path}[{r}]");
+            let Some(row) = row.as_array() else {
+                let message = format!("expected an array of 8 squares, found {}", kind(row));
+                problem(problems, &path, message);
+                continue;
+            };
+            if row.len() != 8 {
+                problem(
+                    problems,
+                    &path,
+                    format!("expected 8 squares, found {}", row.len()),
+                );
+            }
+            for (f, square) in row.iter().enumerate() {
+                if let Err(message) = finite(square) {
+                    problem(problems, &format!("{path}[{f}]"), message);
+                }
+            }
+        }
+    }
+}
+
+fn finite(value: &Value) -> Result<(), String> {
+    match value.as_f64() {
+        Some(number) if (number as f32).is_finite() => Ok(()),
+        Some(number) => Err(format!("{number} is out of range")),
+        None => Err(format!("expected a number, found {}", kind(value))),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Removes the black tables, which are mirrored from the white ones.
+fn strip_black(value: &mut Value) {
+    if let Value::Object(object) = value {
+        // a black table that is not the mirror of the white one is kept
+        let mirror = object
+            .get("white")
+            .and_then(Value::as_array)
+            .map(|ranks| {
+                Value::Array(
+                    ranks
+                        .iter()
+                        .rev()
+                        .cloned()
+                        .collect(),
+                )
+            });
+        if mirror.is_some() && object.get("black") == mirror.as_ref() {
+            object.remove("black");
+        }
+        object
+            .values_mut()
+            .for_each(strip_black);
+    }
+}
+
+fn write_json(json: &mut String, value: &Value, indent: usize) {
+    let pad = "    ".repeat(indent + 1);
+    match value {
+        Value::Object(object) => {
+            json.push_str("{\n");
+            for (i, (key, value)) in object.iter().enumerate() {
+                json.push_str(&format!("{pad}{key:?}: "));
+                write_json(json, value, indent + 1);
+                json.push_str(if i + 1 < object.len() { ",\n" } else { "\n" });
+            }
+            json.push_str(&"    ".repeat(indent));
+            json.push('}');
+        }
+        // a rank of a table, or the passed pawn weights
+        Value::Array(array)
+            if array
+                .iter()
+                .all(Value::is_number) =>
+        {
+            let numbers: Vec<_> = array
+                .iter()
+                .map(|number| number.to_string())
+                .collect();
+            json.push_str(&format!("[{}]", numbers.join(", ")));
+        }
+        Value::Array(array) => {
+            json.push_str("[\n");
+            for (i, value) in array.iter().enumerate() {
+                json.push_str(&pad);
+                write_json(json, value, indent + 1);
+                json.push_str(if i + 1 < array.len() { ",\n" } else { "\n" });
+            }
+            json.push_str(&"    ".repeat(indent));
+            json.push(']');
+        }
+        // f32 weights are rounded so they are not written as 0.15000000596
+        Value::Number(number) => match number.as_f64() {
+            Some(number) => json.push_str(&format!("{}", (number * 1e4).round() / 1e4)),
+            None => json.push_str(&number.to_string()),
+        },
+        value => json.push_str(&value.to_string()),
+    }
 }
 
 impl Index<(Position, Color)> for ValueTable {
@@ -38,66 +691,112 @@ impl Index<(Position, Color)> for ValueTable {
     }
 }
 
-static SETTINGS: Lazy<Value> = Lazy::new(|| {
-    let settings = crate::opt::options().settings_path;
-    let json = std::fs::read_to_string(settings).unwrap();
-    serde_json::from_str(&json).unwrap()
-});
-
-pub static DEFENDED: Lazy<f32> = Lazy::new(|| {
-    SETTINGS["defended_value"]
-        .clone()
-        .pipe(serde_json::from_value)
+#[test]
+fn reports_every_problem() {
+    let mut settings: Value = serde_json::from_str(DEFAULT_SETTINGS).unwrap();
+    settings
+        .as_object_mut()
         .unwrap()
-});
-
-pub static ATTACKED: Lazy<f32> = Lazy::new(|| {
-    SETTINGS["attacked_value"]
-        .clone()
-        .pipe(serde_json::from_value)
+        .remove("queenside_castle");
+    settings["pawn_structure"]["passed"][2] = Value::Null;
+    settings["king_safety"]["attacks"]
+        .as_object_mut()
         .unwrap()
-});
-
-pub static AVAILABLE_MOVES: Lazy<f32> = Lazy::new(|| {
-    SETTINGS["available_moves"]
-        .clone()
-        .pipe(serde_json::from_value)
+        .remove("rook");
+    settings["pawn"]["white"][3]
+        .as_array_mut()
         .unwrap()
-});
+        .pop();
+    settings["king"]["endgame"]["white"][0][5] = Value::String("1".into());
+    let paths: Vec<_> = validate(&settings)
+        .into_iter()
+        .map(|problem| problem.path)
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "$.queenside_castle",
+            "$.pawn_structure.passed[2]",
+            "$.king_safety.attacks.rook",
+            "$.pawn.white[3]",
+            "$.king.endgame.white[0][5]"
+        ]
+    );
+}
 
-pub static PAWN: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["pawn"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
-pub static KING: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["king"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
-pub static QUEEN: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["queen"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
-pub static BISHOP: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["bishop"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
-pub static KNIGHT: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["knight"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
-pub static ROOK: Lazy<ValueTable> = Lazy::new(|| {
-    SETTINGS["rook"]
-        .clone()
-        .pipe(serde_json::from_value)
-        .unwrap()
-});
+#[test]
+fn older_settings() {
+    let mut settings: Value = serde_json::from_str(DEFAULT_SETTINGS).unwrap();
+    let root = settings
+        .as_object_mut()
+        .unwrap();
+    root.remove("pawn_structure");
+    root.remove("king_safety");
+    root.remove("activity");
+    for key in OBSOLETE {
+        root.insert(key.to_string(), 1.0.into());
+    }
+    let (config, ignored) = EvalConfig::parse(&settings.to_string()).unwrap();
+    assert_eq!(config, EvalConfig::default());
+    let paths: Vec<_> = ignored
+        .into_iter()
+        .map(|problem| problem.path)
+        .collect();
+    assert_eq!(
+        paths,
+        ["$.defended_value", "$.attacked_value", "$.available_moves"]
+    );
+}
+
+#[test]
+fn json_round_trip() {
+    let config = EvalConfig::default();
+    let json = config.to_json();
+    assert!(json.contains("\"shield\": 0.15,"));
+    assert!(!json.contains("\"black\""));
+    assert_eq!(EvalConfig::from_json(&json).unwrap(), config);
+
+    let mut asymmetric = config;
+    asymmetric.king.endgame.black[0][4] += 1.0;
+    let json = asymmetric.to_json();
+    assert_eq!(
+        json.matches("\"black\"")
+            .count(),
+        1
+    );
+    assert_eq!(EvalConfig::from_json(&json).unwrap(), asymmetric);
+}
+
+#[test]
+fn mirrored_tables() {
+    let mut rows = [[0.0; 8]; 8];
+    rows[1][2] = 1.0;
+    let white: ValueTable = serde_json::from_value(serde_json::json!({ "white": rows })).unwrap();
+    let black: ValueTable = serde_json::from_value(serde_json::json!({ "black": rows })).unwrap();
+    assert_eq!(white.black[6][2], 1.0);
+    assert_eq!(black.white[6][2], 1.0);
+    assert_eq!(white.white, black.black);
+}
+
+#[test]
+fn default_settings() {
+    let config = EvalConfig::default();
+    let pawn = Piece {
+        kind: Pawn,
+        color: Color::White,
+    };
+    assert_eq!(config.value((pawn, "e2".parse().unwrap()), 1.0), 1.0);
+}
+
+#[test]
+fn tapered_king() {
+    let config = EvalConfig::default();
+    let king = Piece {
+        kind: King,
+        color: Color::White,
+    };
+    let center = "e4".parse().unwrap();
+    let corner = "g1".parse().unwrap();
+    assert!(config.value((king, corner), 1.0) > config.value((king, center), 1.0));
+    assert!(config.value((king, corner), 0.0) < config.value((king, center), 0.0));
+}
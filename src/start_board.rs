@@ -1,9 +1,5 @@
-use std::fmt::{Display};
-
 use crate::piece::*;
 use crate::table::{Board, Castle};
-use Color::*;
-use Kind::*;
 impl Default for Board {
     fn default() -> Self {
         START_BOARD
@@ -13,8 +9,17 @@ impl Default for Board {
 const START_BOARD: Board = {
     let empty = [None, None, None, None, None, None, None, None];
     Board {
-        white_castle: Castle { queenside: true, kingside: true },
-        black_castle: Castle { queenside: true, kingside: true },
+        en_passant: None,
+        white_castle: Castle {
+            queenside: true,
+            kingside: true,
+            castled: None,
+        },
+        black_castle: Castle {
+            queenside: true,
+            kingside: true,
+            castled: None,
+        },
         table: [
             [
                 Some(BL_ROOK),
@@ -64,51 +69,7 @@ const START_BOARD: Board = {
     }
 };
 
-impl Display for Board {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let table = self
-            .table
-            .into_iter()
-            .enumerate();
-        for (r, row) in table {
-            if r == 0 {
-                write!(f, "  ╔═══╦═══╦═══╦═══╦═══╦═══╦═══╦═══╗")?;
-            }
-            write!(f, "\n{} ║", r + 1)?;
-            for option in row {
-                if let Some(piece) = option {
-                    match (piece.kind, piece.color) {
-                        (Bishop, White) => write!(f, " ♗ ║")?,
-                        (Rook, White) => write!(f, " ♖ ║")?,
-                        (King, White) => write!(f, " ♔ ║")?,
-                        (Queen, White) => write!(f, " ♕ ║")?,
-                        (Pawn, White) => write!(f, " ♙ ║")?,
-                        (Knight, White) => write!(f, " ♘ ║")?,
-                        (Bishop, Black) => write!(f, " ♝ ║")?,
-                        (Rook, Black) => write!(f, " ♜ ║")?,
-                        (King, Black) => write!(f, " ♚ ║")?,
-                        (Queen, Black) => write!(f, " ♛ ║")?,
-                        (Pawn, Black) => write!(f, " ♟ ║")?,
-                        (Knight, Black) => write!(f, " ♞ ║")?,
-                    }
-                } else {
-                    write!(f, "   ║")?; 
-                }
-                
-            }
-
-            if r == 7 {
-                writeln!(f, "\n  ╚═══╩═══╩═══╩═══╩═══╩═══╩═══╩═══╝")?;
-                write!(f,   "    A   B   C   D   E   F   G   H")?;
-            } else {
-                write!(f, "\n  ╠═══╬═══╬═══╬═══╬═══╬═══╬═══╬═══╣")?;
-            }
-        }
-
-        Ok(())
-    }
-}
 #[test]
 fn foo() {
-    println!("{START_BOARD}"); 
+    println!("{START_BOARD}");
 }
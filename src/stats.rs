@@ -0,0 +1,72 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::moves::Move;
+
+/// How an iteration of the iterative deepening went, as seen by the main
+/// thread of the search. Only the node count includes the helper threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationStats {
+    pub depth: u32,
+    /// The deepest ply the iteration reached, with the quiescence search.
+    pub seldepth: u32,
+    /// The score from the point of view of the side to move.
+    pub score: f32,
+    pub pv: Vec<Move>,
+    /// The nodes searched by all the threads since the start of the search.
+    pub nodes: u64,
+    /// The nodes of this iteration in the quiescence search, from the
+    /// horizon on.
+    pub qnodes: u64,
+    /// The time since the start of the search.
+    pub elapsed: Duration,
+    /// The lookups of the transposition table in this iteration, and how
+    /// many of them found the position.
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    /// The beta cutoffs of this iteration, and how many of them happened
+    /// on the first move searched.
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    /// The nodes of this iteration divided by those of the previous one,
+    /// `None` for the first iteration.
+    pub branching_factor: Option<f32>,
+}
+
+impl IterationStats {
+    /// Nodes per second.
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64
+            / self
+                .elapsed
+                .as_secs_f64()
+                .max(1e-3)) as u64
+    }
+
+    pub fn tt_hit_rate(&self) -> f32 {
+        self.tt_hits as f32 / self.tt_probes.max(1) as f32
+    }
+
+    /// The share of cutoffs on the first move, which is the better the
+    /// moves are ordered.
+    pub fn first_move_cutoff_rate(&self) -> f32 {
+        self.first_move_cutoffs as f32 / self.cutoffs.max(1) as f32
+    }
+}
+
+/// Receives the statistics of the search after every iteration, see
+/// [`Limits::observer`](crate::minimax::Limits::observer). Only the
+/// minimax search reports them.
+pub trait SearchObserver: Debug + Send + Sync {
+    fn iteration(&self, stats: &IterationStats);
+}
+
+/// Keeps the statistics of every iteration.
+impl SearchObserver for Mutex<Vec<IterationStats>> {
+    fn iteration(&self, stats: &IterationStats) {
+        self.lock()
+            .expect("the statistics are not poisoned")
+            .push(stats.clone());
+    }
+}
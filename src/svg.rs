@@ -0,0 +1,272 @@
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+use crate::moves::{Move, Position};
+use crate::pgn::{Pgn, PgnError};
+use crate::piece::{Color, Kind, Piece};
+use crate::table::Board;
+
+/// A color of the diagram, either `#rgb`, `#rrggbb` or a named color
+/// such as `teal`, so it can be written into the SVG as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgColor(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgColorError(String);
+
+impl Display for SvgColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid color {:?}, expected #rgb, #rrggbb or a color name",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for SvgColorError {}
+
+impl FromStr for SvgColor {
+    type Err = SvgColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = match s.strip_prefix('#') {
+            Some(hex) => {
+                matches!(hex.len(), 3 | 6)
+                    && hex
+                        .chars()
+                        .all(|c| c.is_ascii_hexdigit())
+            }
+            None => {
+                !s.is_empty()
+                    && s.chars()
+                        .all(|c| c.is_ascii_alphabetic())
+            }
+        };
+        match valid {
+            true => Ok(SvgColor(s.to_string())),
+            false => Err(SvgColorError(s.to_string())),
+        }
+    }
+}
+
+impl Display for SvgColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    pub light: SvgColor,
+    pub dark: SvgColor,
+    /// Draw the rank and file labels around the board.
+    pub coordinates: bool,
+    /// The side whose pieces are drawn at the bottom of the board.
+    pub orientation: Color,
+    pub square_size: u32,
+    pub arrows: Vec<Move>,
+    pub arrow_color: SvgColor,
+    pub highlights: Vec<Position>,
+    pub highlight_color: SvgColor,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            light: SvgColor("#f0d9b5".to_string()),
+            dark: SvgColor("#b58863".to_string()),
+            coordinates: true,
+            orientation: Color::White,
+            square_size: 45,
+            arrows: vec![],
+            arrow_color: SvgColor("#15781b".to_string()),
+            highlights: vec![],
+            highlight_color: SvgColor("#cdd26a".to_string()),
+        }
+    }
+}
+
+/// Every piece is drawn with the solid glyph, filled with the color of
+/// its side, so both sides have the same shape. The variation selector
+/// prevents some fonts from rendering the pawn as an emoji.
+fn glyph(kind: Kind) -> &'static str {
+    match kind {
+        Kind::King => "♚",
+        Kind::Queen => "♛",
+        Kind::Rook => "♜",
+        Kind::Bishop => "♝",
+        Kind::Knight => "♞",
+        Kind::Pawn => "♟\u{fe0e}",
+    }
+}
+
+impl Board {
+    /// Renders the board as a self-contained SVG document.
+    pub fn svg(&self, options: &SvgOptions) -> String {
+        let size = options.square_size as f32;
+        let margin = if options.coordinates { size / 2.0 } else { 0.0 };
+        let total = 8.0 * size + 2.0 * margin;
+        let flipped = options.orientation == Color::Black;
+        let order = |i: isize| if flipped { 7 - i } else { i };
+        // top left corner of a square
+        let corner = |pos: Position| {
+            (
+                margin + order(pos.file) as f32 * size,
+                margin + order(pos.rank) as f32 * size,
+            )
+        };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{total}" height="{total}" viewBox="0 0 {total} {total}">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<defs><marker id="arrowhead" viewBox="0 0 10 10" refX="5" refY="5" markerWidth="3" markerHeight="3" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="{}"/></marker></defs>"#,
+            options.arrow_color
+        );
+        if options.coordinates {
+            let _ = writeln!(
+                svg,
+                r##"<rect width="{total}" height="{total}" fill="#404040"/>"##
+            );
+        }
+        for (rank, file) in (0..8).flat_map(|rank| (0..8).map(move |file| (rank, file))) {
+            let pos = Position { rank, file };
+            let (x, y) = corner(pos);
+            let fill = if options
+                .highlights
+                .contains(&pos)
+            {
+                &options.highlight_color
+            } else if (rank + file) % 2 == 0 {
+                &options.light
+            } else {
+                &options.dark
+            };
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{size}" height="{size}" fill="{fill}"/>"#
+            );
+        }
+        if options.coordinates {
+            for i in 0..8 {
+                let file = (b'a' + i as u8) as char;
+                let rank = 8 - i;
+                let (x, _) = corner(Position { rank: 0, file: i });
+                let (_, y) = corner(Position { rank: i, file: 0 });
+                let _ = writeln!(
+                    svg,
+                    r##"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" fill="#e0e0e0" text-anchor="middle" dominant-baseline="central">{file}</text>"##,
+                    x + size / 2.0,
+                    total - margin / 2.0,
+                    margin * 0.6
+                );
+                let _ = writeln!(
+                    svg,
+                    r##"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" fill="#e0e0e0" text-anchor="middle" dominant-baseline="central">{rank}</text>"##,
+                    margin / 2.0,
+                    y + size / 2.0,
+                    margin * 0.6
+                );
+            }
+        }
+        for (rank, file) in (0..8).flat_map(|rank| (0..8).map(move |file| (rank, file))) {
+            let pos = Position { rank, file };
+            let Some(Piece { kind, color }) = self[pos] else {
+                continue;
+            };
+            let (x, y) = corner(pos);
+            let (fill, stroke) = match color {
+                Color::White => ("#ffffff", "#000000"),
+                Color::Black => ("#000000", "#000000"),
+            };
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-family="DejaVu Sans, Segoe UI Symbol, serif" font-size="{}" fill="{fill}" stroke="{stroke}" stroke-width="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                x + size / 2.0,
+                y + size / 2.0,
+                size * 0.8,
+                size / 45.0,
+                glyph(kind)
+            );
+        }
+        for arrow in &options.arrows {
+            let (x1, y1) = corner(arrow.from);
+            let (x2, y2) = corner(arrow.to);
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" stroke-linecap="round" opacity="0.8" marker-end="url(#arrowhead)"/>"#,
+                x1 + size / 2.0,
+                y1 + size / 2.0,
+                x2 + size / 2.0,
+                y2 + size / 2.0,
+                options.arrow_color,
+                size / 5.0
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Renders one diagram per position of the game, starting with the initial
+/// position. The squares of the last move are highlighted on top of the
+/// highlights given in the options.
+pub fn pgn_svgs(pgn: &Pgn, options: &SvgOptions) -> Result<Vec<String>, PgnError> {
+    let plies = pgn.plies()?;
+    let svgs = plies
+        .iter()
+        .map(|ply| {
+            let mut options = options.clone();
+            if let Some(last) = ply.last_move {
+                options
+                    .highlights
+                    .extend([last.from, last.to]);
+            }
+            ply.board.svg(&options)
+        })
+        .collect();
+    Ok(svgs)
+}
+
+#[test]
+fn svg_diagram() {
+    let options = SvgOptions {
+        arrows: vec![Move {
+            from: "e2".parse().unwrap(),
+            to: "e4".parse().unwrap(),
+            promotion: None,
+        }],
+        ..Default::default()
+    };
+    let svg = Board::default().svg(&options);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg
+        .trim_end()
+        .ends_with("</svg>"));
+    assert_eq!(
+        svg.matches("<rect x=")
+            .count(),
+        64
+    );
+    assert_eq!(svg.matches("♟").count(), 16);
+    assert_eq!(svg.matches("<line").count(), 1);
+
+    assert_eq!(
+        "#0a0B0c"
+            .parse::<SvgColor>()
+            .map(|color| color.to_string()),
+        Ok("#0a0B0c".to_string())
+    );
+    assert!("teal"
+        .parse::<SvgColor>()
+        .is_ok());
+    for color in ["#12345", "#ggg", "", "red\" onload=\"x"] {
+        assert!(color
+            .parse::<SvgColor>()
+            .is_err());
+    }
+}
@@ -1,32 +1,155 @@
-use crate::settings::*;
 use crate::{
-    moves::{Move, Play, Position},
+    moves::{playable, Move, Play, Position},
     piece::{Color, Kind, Piece},
 };
+use smallvec::SmallVec;
 use tap::prelude::*;
 use Kind::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Board {
     pub table: [[Option<Piece>; 8]; 8],
     pub black_castle: Castle,
     pub white_castle: Castle,
+    /// The square a pawn skipped over with a double jump in the last move.
+    pub en_passant: Option<Position>,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Castle {
     pub kingside: bool,
     pub queenside: bool,
+    /// The side the king castled to, if it already did.
+    pub castled: Option<CastleSide>,
 }
 
-fn cast<'a, T: Iterator<Item = Position> + 'a>(obj: T) -> Box<dyn Iterator<Item = Position> + 'a> {
-    Box::new(obj) as Box<dyn Iterator<Item = Position>>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastleSide {
+    Kingside,
+    Queenside,
 }
-type Positions<'a> = Box<dyn Iterator<Item = Position> + 'a>;
 
 impl Board {
+    /// Applies a move, taking care of castling, en passant,
+    /// promotions and the castling rights.
     pub fn apply(&mut self, move_: Move) {
-        let piece = self[move_.from].take();
-        self[move_.to] = piece;
+        let Some(piece) = self[move_.from].take() else {
+            return;
+        };
+        let (from, to) = (move_.from, move_.to);
+        if piece.kind == Pawn && Some(to) == self.en_passant && self[to].is_none() {
+            self[Position {
+                rank: from.rank,
+                file: to.file,
+            }] = None;
+        }
+        if piece.kind == King && (to.file - from.file).abs() == 2 {
+            let (rook_from, rook_to) = if to.file > from.file { (7, 5) } else { (0, 3) };
+            let rook = self[Position::from((from.rank, rook_from))].take();
+            self[Position::from((from.rank, rook_to))] = rook;
+        }
+        self.en_passant =
+            (piece.kind == Pawn && (to.rank - from.rank).abs() == 2).then_some(Position {
+                rank: (from.rank + to.rank) / 2,
+                file: from.file,
+            });
+
+        if piece.kind == King {
+            let castle = self.castle_mut(piece.color);
+            *castle = Castle {
+                kingside: false,
+                queenside: false,
+                castled: match to.file - from.file {
+                    2 => Some(CastleSide::Kingside),
+                    -2 => Some(CastleSide::Queenside),
+                    _ => castle.castled,
+                },
+            };
+        }
+        for color in [Color::White, Color::Black] {
+            let rank = color.back_rank();
+            let castle = self.castle_mut(color);
+            for square in [from, to] {
+                castle.kingside &= square != Position::from((rank, 7));
+                castle.queenside &= square != Position::from((rank, 0));
+            }
+        }
+
+        self[to] = Some(Piece {
+            kind: move_
+                .promotion
+                .unwrap_or(piece.kind),
+            color: piece.color,
+        });
+    }
+
+    /// The same position with the colors swapped and the ranks flipped,
+    /// so white's pieces stand where black's did and vice versa.
+    pub fn mirror(&self) -> Board {
+        let mut table = self.table;
+        table.reverse();
+        for piece in table
+            .iter_mut()
+            .flatten()
+            .flatten()
+        {
+            piece.color = piece.color.opposite();
+        }
+        Board {
+            table,
+            white_castle: self.black_castle,
+            black_castle: self.white_castle,
+            en_passant: self
+                .en_passant
+                .map(|pos| Position {
+                    rank: 7 - pos.rank,
+                    file: pos.file,
+                }),
+        }
+    }
+
+    pub fn castle(&self, color: Color) -> Castle {
+        match color {
+            Color::White => self.white_castle,
+            Color::Black => self.black_castle,
+        }
+    }
+
+    pub fn castle_mut(&mut self, color: Color) -> &mut Castle {
+        match color {
+            Color::White => &mut self.white_castle,
+            Color::Black => &mut self.black_castle,
+        }
+    }
+
+    /// The plays that can actually be made by `turn`, i.e. pseudo legal
+    /// moves that do not leave the king in check.
+    pub fn legal_plays(&self, turn: Color) -> impl Iterator<Item = Play> + '_ {
+        self.moves(turn)
+            .filter(|&play| playable(play))
+            .filter(move |play| {
+                let mut board = *self;
+                board.apply(play.r#move());
+                !board.in_check(turn)
+            })
+    }
+
+    pub fn legal_moves(&self, turn: Color) -> impl Iterator<Item = Move> + '_ {
+        self.legal_plays(turn)
+            .map(Play::r#move)
+    }
+
+    /// Counts the leaf nodes of the legal move tree, used to validate move generation.
+    pub fn perft(&self, turn: Color, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        self.legal_moves(turn)
+            .map(|r#move| {
+                let mut board = *self;
+                board.apply(r#move);
+                board.perft(turn.opposite(), depth - 1)
+            })
+            .sum()
     }
 
     // pub fn apply(&mut self, play: Play) -> Option<f32> {
@@ -51,13 +174,87 @@ impl Board {
             .get(pos.file as usize)?
     }
 
+    pub fn king(&self, color: Color) -> Option<Position> {
+        self.colored_pieces(color)
+            .find(|(piece, _)| piece.kind == King)
+            .map(|(_, pos)| pos)
+    }
+
+    pub fn in_check(&self, color: Color) -> bool {
+        self.king(color)
+            .map(|pos| self.is_attacked(pos, color.opposite()))
+            .unwrap_or(false)
+    }
+
+    /// Returns whether any piece of color `by` attacks the square `pos`.
+    /// This does not depend on move generation, so it can be used to
+    /// validate the moves it produces.
+    pub fn is_attacked(&self, pos: Position, by: Color) -> bool {
+        let is = |piece: Option<Piece>, kinds: &[Kind]| {
+            piece
+                .filter(|piece| piece.color == by)
+                .map(|piece| kinds.contains(&piece.kind))
+                .unwrap_or(false)
+        };
+        let knight = [
+            (1, 2),
+            (2, 1),
+            (-1, 2),
+            (-2, 1),
+            (1, -2),
+            (2, -1),
+            (-1, -2),
+            (-2, -1),
+        ];
+        let king = [
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+        ];
+        let offset = |(rank, file): (isize, isize)| (pos.rank + rank, pos.file + file);
+        let pawn = [(-by.pawn_dir(), 1), (-by.pawn_dir(), -1)];
+
+        let leapers = knight
+            .map(offset)
+            .iter()
+            .any(|&to| is(self.get(to.into()), &[Knight]))
+            || king
+                .map(offset)
+                .iter()
+                .any(|&to| is(self.get(to.into()), &[King]))
+            || pawn
+                .map(offset)
+                .iter()
+                .any(|&to| is(self.get(to.into()), &[Pawn]));
+
+        let sliders = king
+            .iter()
+            .any(|&(rank, file)| {
+                let kinds: &[Kind] = if rank == 0 || file == 0 {
+                    &[Rook, Queen]
+                } else {
+                    &[Bishop, Queen]
+                };
+                (1..8)
+                    .map(|i| Position::from((pos.rank + rank * i, pos.file + file * i)))
+                    .take_while(|to| (0..8).contains(&to.rank) && (0..8).contains(&to.file))
+                    .find_map(|to| self[to])
+                    .pipe(|piece| is(piece, kinds))
+            });
+        leapers || sliders
+    }
+
     pub fn colored_pieces<'a>(
         &'a self,
         color: Color,
     ) -> impl Iterator<Item = (Piece, Position)> + 'a {
         (0..8)
-            .map(|rank| (0..8).map(move |file| (rank, file)))
-            .flatten()
+            .flat_map(|rank| (0..8).map(move |file| (rank, file)))
             .map(Position::from)
             .filter_map(move |pos| (self[pos]?, pos).pipe(Some))
             .filter(move |(piece, _)| piece.color == color)
@@ -65,8 +262,7 @@ impl Board {
     pub fn moves<'a>(&'a self, turn: Color) -> impl Iterator<Item = Play> + 'a {
         self.colored_pieces(turn)
             .map(|(_, pos)| pos)
-            .map(|pos| self.moves_for(pos))
-            .flatten()
+            .flat_map(|pos| self.moves_for(pos))
     }
 
     #[allow(unreachable_patterns)]
@@ -74,7 +270,7 @@ impl Board {
         use itertools::Either::*;
         self[pos]
             .into_iter()
-            .map(move |piece| match piece.kind {
+            .flat_map(move |piece| match piece.kind {
                 Bishop => self
                     .bishop_moves(pos, piece.color)
                     .pipe(Left)
@@ -91,7 +287,7 @@ impl Board {
                     .pipe(Right)
                     .pipe(Left),
                 Knight => self
-                    .king_moves(pos, piece.color)
+                    .knight_moves(pos, piece.color)
                     .pipe(Left)
                     .pipe(Right)
                     .pipe(Right),
@@ -117,7 +313,6 @@ impl Board {
                     .pipe(Right)
                     .pipe(Right),
             })
-            .flatten()
     }
     /// # Bishop moves
     /// ready
@@ -125,6 +320,20 @@ impl Board {
         self.walk(pos, color, -1, -1)
             .chain(self.walk(pos, color, -1, 1))
             .chain(self.walk(pos, color, 1, -1))
+            .chain(self.walk(pos, color, 1, 1))
+    }
+    /// # Knight
+    /// ready
+    fn knight_moves(&self, pos: Position, color: Color) -> impl Iterator<Item = Play> {
+        self.relative(pos, color, 1, 2)
+            .into_iter()
+            .chain(self.relative(pos, color, 2, 1))
+            .chain(self.relative(pos, color, -1, 2))
+            .chain(self.relative(pos, color, -2, 1))
+            .chain(self.relative(pos, color, 1, -2))
+            .chain(self.relative(pos, color, 2, -1))
+            .chain(self.relative(pos, color, -1, -2))
+            .chain(self.relative(pos, color, -2, -1))
     }
     /// # Rook
     /// ready
@@ -138,7 +347,6 @@ impl Board {
     /// ## ready
     /// * diagonal moves
     /// * vertical and horizontal moves
-
     fn queen_moves<'a>(&'a self, pos: Position, color: Color) -> impl Iterator<Item = Play> + 'a {
         self.bishop_moves(pos, color)
             .chain(self.rook_moves(pos, color))
@@ -146,7 +354,6 @@ impl Board {
     /// # King
     /// ## ready
     /// * basic moves
-    /// ## missing
     /// * castling
     fn king_moves<'a>(&'a self, pos: Position, color: Color) -> impl Iterator<Item = Play> + 'a {
         self.relative(pos, color, 0, 1)
@@ -158,42 +365,114 @@ impl Board {
             .chain(self.relative(pos, color, -1, 0))
             .chain(self.relative(pos, color, -1, 1))
             .chain(self.relative(pos, color, -1, -1))
+            .chain(self.castles(pos, color))
+    }
+
+    /// Castling requires the squares between the king and the rook to
+    /// be empty, and the king must not be in check or pass through
+    /// an attacked square.
+    fn castles(&self, pos: Position, color: Color) -> SmallVec<[Play; 2]> {
+        let mut castles = SmallVec::new();
+        let rank = color.back_rank();
+        if pos != Position::from((rank, 4)) {
+            return castles;
+        }
+        let rook = Some(Piece { kind: Rook, color });
+        let empty = |files: &[isize]| {
+            files
+                .iter()
+                .all(|&file| self[Position::from((rank, file))].is_none())
+        };
+        let safe = |files: &[isize]| {
+            files
+                .iter()
+                .all(|&file| !self.is_attacked(Position::from((rank, file)), color.opposite()))
+        };
+        let castle = self.castle(color);
+        let to = |file| Move {
+            from: pos,
+            to: Position::from((rank, file)),
+            promotion: None,
+        };
+        if castle.kingside
+            && self[Position::from((rank, 7))] == rook
+            && empty(&[5, 6])
+            && safe(&[4, 5, 6])
+        {
+            castles.push(Play::RightCastle(to(6)));
+        }
+        if castle.queenside
+            && self[Position::from((rank, 0))] == rook
+            && empty(&[1, 2, 3])
+            && safe(&[4, 3, 2])
+        {
+            castles.push(Play::LeftCastle(to(2)));
+        }
+        castles
     }
     /// # Pawns
     /// ## ready
     /// 1. double initial jump
     /// 2. sideways capture
     /// 3. upwards move
-    ///
-    /// ## missing
-    /// 1. pawn passant
-    /// 2. promotion
-    fn pawn_moves<'a>(&'a self, pos: Position, color: Color) -> impl Iterator<Item = Play> {
+    /// 4. pawn passant
+    /// 5. promotion
+    fn pawn_moves(&self, pos: Position, color: Color) -> impl Iterator<Item = Play> + '_ {
+        let single = self.moves_only(pos, color, color.pawn_dir(), 0);
         self.capture_only(pos, color, color.pawn_dir(), 1)
             .into_iter()
             .chain(self.capture_only(pos, color, color.pawn_dir(), -1))
-            .chain(self.moves_only(pos, color, color.pawn_dir(), 0))
+            .chain(self.en_passant(pos, color))
+            .chain(single)
             .chain({
-                if pos.rank == color.pawn_start() {
+                if pos.rank == color.pawn_start() && single.is_some() {
                     self.moves_only(pos, color, 2 * color.pawn_dir(), 0)
                 } else {
                     None
                 }
             })
-    }
-    #[inline]
-    fn is_available(&self, pos: Position, color: Color) -> bool {
-        self.get(pos)
-            .map(|piece| piece.color != color)
-            .unwrap_or(true)
+            .flat_map(move |play| self.promotions(play, color))
     }
 
-    fn is_capture(&self, pos: Position, color: Color) -> bool {
-        self[pos]
-            .map(|piece| piece.color != color)
-            .unwrap_or(false)
+    fn en_passant(&self, pos: Position, color: Color) -> Option<Play> {
+        let to = self.en_passant?;
+        if to.rank - pos.rank != color.pawn_dir() || (to.file - pos.file).abs() != 1 {
+            return None;
+        }
+        let taken = Piece {
+            kind: Pawn,
+            color: color.opposite(),
+        };
+        Play::Capture(
+            Move {
+                to,
+                from: pos,
+                promotion: None,
+            },
+            taken,
+        )
+        .pipe(Some)
     }
 
+    /// Expands a pawn play reaching the last rank into one play per promotion.
+    fn promotions(&self, play: Play, color: Color) -> SmallVec<[Play; 4]> {
+        let last_rank = color.opposite().back_rank();
+        let promote = |r#move: Move, kind| Move {
+            promotion: Some(kind),
+            ..r#move
+        };
+        match play {
+            Play::Capture(r#move, piece) if r#move.to.rank == last_rank => {
+                [Queen, Rook, Bishop, Knight]
+                    .map(|kind| Play::Capture(promote(r#move, kind), piece))
+                    .into()
+            }
+            Play::Move(r#move) if r#move.to.rank == last_rank => [Queen, Rook, Bishop, Knight]
+                .map(|kind| Play::Move(promote(r#move, kind)))
+                .into(),
+            play => smallvec::smallvec![play],
+        }
+    }
     /// This is used to describe the movements of pieces that can move in some relative
     /// direction but they cannot capture. This is used to describe the movements of pawns.
     fn moves_only(&self, from: Position, color: Color, rank: isize, file: isize) -> Option<Play> {
@@ -224,7 +503,14 @@ impl Board {
             rank: from.rank + rank,
             file: from.file + file,
         };
-        let r#move = Move { to, from };
+        if !to.in_bounds() {
+            return None;
+        }
+        let r#move = Move {
+            to,
+            from,
+            promotion: None,
+        };
         match self.get(to) {
             Some(piece) if piece.color == color => Play::Defense(r#move, piece),
             Some(piece) => Play::Capture(r#move, piece),
@@ -250,10 +536,14 @@ impl Board {
                     rank: iterpos.rank - rank,
                     file: iterpos.file - file,
                 };
-                pos == iterpos || self[prev].is_some()
+                pos == prev || self[prev].is_none()
             })
             .map(move |to| {
-                let r#move = Move { to, from: pos };
+                let r#move = Move {
+                    to,
+                    from: pos,
+                    promotion: None,
+                };
                 match self[to] {
                     Some(piece) if color == piece.color => Play::Defense(r#move, piece),
                     Some(piece) => Play::Capture(r#move, piece),
@@ -277,3 +567,29 @@ impl std::ops::IndexMut<Position> for Board {
 
 #[test]
 fn foo() {}
+
+#[test]
+fn perft() {
+    let positions = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            3,
+            8902,
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+            97862,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+            9467,
+        ),
+    ];
+    for (fen, depth, nodes) in positions {
+        let (board, turn) = Board::from_fen(fen).unwrap();
+        assert_eq!(board.perft(turn, depth), nodes, "{fen}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use crate::moves::Move;
+
+/// A node of the tree explored by the last iteration of the search, kept
+/// to see why a move was chosen. Scores and bounds are from the point of
+/// view of the side to move at the node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchTree {
+    /// The move that led to the node, `None` at the root.
+    pub r#move: Option<Move>,
+    pub score: f32,
+    /// The window the node was searched with.
+    pub alpha: f32,
+    pub beta: f32,
+    /// The depth left to search at the node.
+    pub depth: u32,
+    /// The node was not searched by the last iteration, because a sibling
+    /// before it caused a cutoff. Its score comes from an earlier one.
+    pub pruned: bool,
+    /// The score came from the transposition table.
+    pub tt_hit: bool,
+    pub children: Vec<SearchTree>,
+}
+
+impl SearchTree {
+    /// The tree as JSON, with moves in coordinate notation. Infinite
+    /// bounds are written as `null`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "move": self.r#move.map(|r#move| r#move.to_string()),
+            "score": self.score,
+            "alpha": self.alpha,
+            "beta": self.beta,
+            "depth": self.depth,
+            "pruned": self.pruned,
+            "tt_hit": self.tt_hit,
+            "children": self
+                .children
+                .iter()
+                .map(SearchTree::to_json)
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The tree as a Graphviz graph. Pruned nodes are dashed and the
+    /// ones found in the transposition table are filled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [shape=box, fontname=monospace];\n");
+        let mut next = 0;
+        self.write_dot(&mut dot, &mut next);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the node as `n<id>`, then its children, and returns the id.
+    fn write_dot(&self, dot: &mut String, next: &mut usize) -> usize {
+        let id = *next;
+        *next += 1;
+        let bound = |bound: f32| match bound {
+            f32::INFINITY => "inf".to_string(),
+            f32::NEG_INFINITY => "-inf".to_string(),
+            bound => format!("{bound:.2}"),
+        };
+        let mut style = vec![];
+        if self.pruned {
+            style.push("dashed");
+        }
+        if self.tt_hit {
+            style.push("filled");
+        }
+        writeln!(
+            dot,
+            "    n{id} [label=\"{}\\n{:.2} [{}, {}] d{}\", style=\"{}\"];",
+            self.r#move
+                .map_or("root".to_string(), |r#move| r#move.to_string()),
+            self.score,
+            bound(self.alpha),
+            bound(self.beta),
+            self.depth,
+            style.join(",")
+        )
+        .expect("writing to a string does not fail");
+        for child in &self.children {
+            let child = child.write_dot(dot, next);
+            writeln!(dot, "    n{id} -> n{child};").expect("writing to a string does not fail");
+        }
+        id
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::moves::{Move, Position};
+use crate::piece::Kind;
+
+/// How the score of an entry relates to the real score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high, the real score is at least this one.
+    Lower,
+    /// The search failed low, the real score is at most this one.
+    Upper,
+}
+
+/// The result of a search of a position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    /// The score from the point of view of the side to move, with mates
+    /// counted from this position rather than from the root.
+    pub score: f32,
+    /// The depth the position was searched to.
+    pub depth: u32,
+    pub bound: Bound,
+    pub r#move: Option<Move>,
+}
+
+/// The number of entries of a table nobody asked a size for, 1 MB.
+pub const DEFAULT_SIZE: usize = 1 << 16;
+
+/// A fixed size table of search results shared by the threads of the
+/// search, indexed by the Polyglot key of the position.
+///
+/// Entries are written without locks. Each slot keeps the key xored with
+/// the data next to the data itself, so an entry torn by two threads
+/// writing at once does not match its key and is ignored.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        TranspositionTable {
+            slots: (0..size.max(1))
+                .map(|_| Default::default())
+                .collect(),
+        }
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let [check, data] = self.slot(key);
+        let data = data.load(Ordering::Relaxed);
+        if check.load(Ordering::Relaxed) ^ data != key || data == 0 {
+            return None;
+        }
+        Some(unpack(data))
+    }
+
+    /// Saves an entry, unless the slot holds a deeper search of the same
+    /// position.
+    pub fn store(&self, key: u64, entry: Entry) {
+        if self
+            .probe(key)
+            .is_some_and(|old| old.depth > entry.depth)
+        {
+            return;
+        }
+        let [check, data] = self.slot(key);
+        let packed = pack(entry);
+        check.store(key ^ packed, Ordering::Relaxed);
+        data.store(packed, Ordering::Relaxed);
+    }
+
+    /// Forgets every entry, for a new game.
+    pub fn clear(&self) {
+        for [check, data] in &self.slots {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+const PROMOTIONS: [Kind; 4] = [Kind::Queen, Kind::Rook, Kind::Bishop, Kind::Knight];
+
+/// Packs an entry as the bits of the score, then 8 bits of depth, 2 of
+/// bound and 16 of move. A valid entry is never 0, as the bound is 1 to 3.
+fn pack(entry: Entry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let r#move = entry
+        .r#move
+        .map_or(0, |r#move| {
+            let square = |pos: Position| (pos.rank * 8 + pos.file) as u64;
+            let promotion = r#move
+                .promotion
+                .and_then(|kind| {
+                    PROMOTIONS
+                        .iter()
+                        .position(|&promotion| promotion == kind)
+                })
+                .map_or(0, |i| i as u64 + 1);
+            1 << 15 | promotion << 12 | square(r#move.from) << 6 | square(r#move.to)
+        });
+    (entry.score.to_bits() as u64) << 32
+        | (entry.depth.min(255) as u64) << 24
+        | bound << 16
+        | r#move
+}
+
+fn unpack(data: u64) -> Entry {
+    let position = |square: u64| Position {
+        rank: (square / 8) as isize,
+        file: (square % 8) as isize,
+    };
+    let r#move = (data & 1 << 15 != 0).then(|| Move {
+        from: position(data >> 6 & 63),
+        to: position(data & 63),
+        promotion: match data >> 12 & 7 {
+            0 => None,
+            i => Some(PROMOTIONS[i as usize - 1]),
+        },
+    });
+    Entry {
+        score: f32::from_bits((data >> 32) as u32),
+        depth: (data >> 24 & 255) as u32,
+        bound: match data >> 16 & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+        r#move,
+    }
+}
+
+#[test]
+fn store_and_probe() {
+    let table = TranspositionTable::new(64);
+    let entry = Entry {
+        score: -1.25,
+        depth: 3,
+        bound: Bound::Lower,
+        r#move: Some(Move {
+            from: Position { rank: 1, file: 6 },
+            to: Position { rank: 0, file: 7 },
+            promotion: Some(Kind::Knight),
+        }),
+    };
+    table.store(1234, entry);
+    assert_eq!(table.probe(1234), Some(entry));
+    // another position in the same slot
+    assert_eq!(table.probe(1234 + 64), None);
+    // a shallower search does not replace a deeper one
+    table.store(
+        1234,
+        Entry {
+            depth: 1,
+            r#move: None,
+            ..entry
+        },
+    );
+    assert_eq!(table.probe(1234), Some(entry));
+    table.clear();
+    assert_eq!(table.probe(1234), None);
+}
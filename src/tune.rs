@@ -0,0 +1,372 @@
+use std::fmt::Display;
+
+use crate::eval::evaluate;
+use crate::fen::FenError;
+use crate::piece::Color;
+use crate::settings::{EvalConfig, PieceTables, ValueTable};
+use crate::table::Board;
+
+/// A position labeled with the result of the game it was taken from.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub board: Board,
+    /// 1 if white won the game, 0.5 for a draw and 0 if black won.
+    pub result: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleError {
+    Fen { line: usize, error: FenError },
+    MissingResult { line: usize },
+}
+
+impl Display for SampleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleError::Fen { line, error } => write!(f, "line {line}: {error}"),
+            SampleError::MissingResult { line } => {
+                write!(f, "line {line}: the position does not have a result")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SampleError {}
+
+/// Parses labeled positions, one per line, either in EPD with the result
+/// given as `c9 "1-0";` or `[1.0]`, or as CSV with the FEN followed by the
+/// result. Empty lines, comments starting with `#` and a CSV header are
+/// skipped.
+pub fn parse_samples(text: &str) -> Result<Vec<Sample>, SampleError> {
+    let mut samples = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let number = i + 1;
+        if line.is_empty()
+            || line.starts_with('#')
+            || line
+                .to_lowercase()
+                .starts_with("fen,")
+        {
+            continue;
+        }
+        // EPD opcodes may have commas too, so CSV needs a single one
+        // followed by nothing but the result
+        let csv = line
+            .split_once(',')
+            .filter(|(_, result)| plain_result(result));
+        let (fen, rest) = match csv {
+            Some((fen, result)) => (fen.to_string(), result.to_string()),
+            None => {
+                // EPD has only the first four fields of a FEN, and the
+                // fields may be separated by any run of whitespace
+                let fields: Vec<_> = line
+                    .split_whitespace()
+                    .collect();
+                let (fen, rest) = fields.split_at(fields.len().min(4));
+                (fen.join(" "), rest.join(" "))
+            }
+        };
+        let (board, _) = Board::from_fen(&fen).map_err(|error| SampleError::Fen {
+            line: number,
+            error,
+        })?;
+        let result = result(&rest).ok_or(SampleError::MissingResult { line: number })?;
+        samples.push(Sample { board, result });
+    }
+    Ok(samples)
+}
+
+/// Whether the text is only a result, as CSV gives it.
+fn plain_result(text: &str) -> bool {
+    let text = text.trim();
+    ["1/2-1/2", "1-0", "0-1"].contains(&text) || text.parse::<f32>().is_ok()
+}
+
+fn result(text: &str) -> Option<f32> {
+    let text = text.trim();
+    for (token, result) in [("1/2-1/2", 0.5), ("1-0", 1.0), ("0-1", 0.0)] {
+        if text.contains(token) {
+            return Some(result);
+        }
+    }
+    text.trim_start_matches('[')
+        .trim_end_matches([']', ';'])
+        .parse()
+        .ok()
+        .filter(|result| (0.0..=1.0).contains(result))
+}
+
+/// The expected result of the game for white given its evaluation.
+fn sigmoid(score: f32, k: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf(-k * score / 4.0))
+}
+
+/// The mean squared error between the results and the results predicted
+/// by the static evaluation.
+pub fn error(samples: &[Sample], config: &EvalConfig, k: f32) -> f32 {
+    let sum: f32 = samples
+        .iter()
+        .map(|sample| {
+            let score = evaluate(&sample.board, Color::White, config);
+            (sample.result - sigmoid(score, k)).powi(2)
+        })
+        .sum();
+    sum / samples.len().max(1) as f32
+}
+
+/// Finds the scaling constant of the sigmoid that best fits the results
+/// with the current evaluation, by golden section search.
+pub fn fit_k(samples: &[Sample], config: &EvalConfig) -> f32 {
+    let scores: Vec<_> = samples
+        .iter()
+        .map(|sample| evaluate(&sample.board, Color::White, config))
+        .collect();
+    let error = |k: f32| {
+        scores
+            .iter()
+            .zip(samples)
+            .map(|(&score, sample)| (sample.result - sigmoid(score, k)).powi(2))
+            .sum::<f32>()
+    };
+    let ratio = (5f32.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.0f32, 10.0f32);
+    for _ in 0..50 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if error(a) < error(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TuneOptions {
+    /// The maximum number of passes over all the weights.
+    pub iterations: usize,
+    /// How much each weight is moved at a time, in pawns.
+    pub step: f32,
+}
+
+impl Default for TuneOptions {
+    fn default() -> Self {
+        TuneOptions {
+            iterations: 10,
+            step: 0.05,
+        }
+    }
+}
+
+/// A weight being tuned, with its mirror when it is a square of a white
+/// table that the black table mirrors.
+type Weight<'a> = (&'a mut f32, Option<&'a mut f32>);
+
+/// Tunes every weight of the configuration by local search, moving one
+/// weight at a time by `step` while the error keeps decreasing. The black
+/// tables that mirror the white ones are moved along with them, the others
+/// are tuned on their own. `progress` is called after every pass with its
+/// error.
+pub fn tune(
+    samples: &[Sample],
+    config: &EvalConfig,
+    options: TuneOptions,
+    mut progress: impl FnMut(usize, f32),
+) -> EvalConfig {
+    let k = fit_k(samples, config);
+    let mut config = config.clone();
+    let mirrored = mirrored(&config);
+    let count = weights(&mut config, &mirrored).len();
+    let step = options.step;
+
+    let mut best = error(samples, &config, k);
+    for iteration in 0..options.iterations {
+        let mut improved = false;
+        for i in 0..count {
+            let mut kept = false;
+            for delta in [step, -2.0 * step] {
+                shift(&mut config, &mirrored, i, delta);
+                let error = error(samples, &config, k);
+                if error < best {
+                    best = error;
+                    kept = true;
+                    break;
+                }
+            }
+            if !kept {
+                // neither direction helped
+                shift(&mut config, &mirrored, i, step);
+            }
+            improved |= kept;
+        }
+        progress(iteration + 1, best);
+        if !improved {
+            break;
+        }
+    }
+    config
+}
+
+/// Moves the `i`th weight by `delta`, along with its mirror.
+fn shift(config: &mut EvalConfig, mirrored: &[bool], i: usize, delta: f32) {
+    let (weight, mirror) = weights(config, mirrored).swap_remove(i);
+    *weight += delta;
+    if let Some(mirror) = mirror {
+        *mirror += delta;
+    }
+}
+
+/// Whether the black table mirrors the white one, for the midgame and
+/// endgame tables of each piece in the order [`weights`] lists them.
+fn mirrored(config: &EvalConfig) -> Vec<bool> {
+    [
+        &config.pawn,
+        &config.knight,
+        &config.bishop,
+        &config.rook,
+        &config.queen,
+        &config.king,
+    ]
+    .into_iter()
+    .flat_map(|tables| [&tables.midgame, &tables.endgame])
+    .map(|table| table.black == ValueTable::mirrored(table.white).black)
+    .collect()
+}
+
+/// The tunable weights, always in the same order. The king-safety
+/// `min_material` is a threshold rather than a score, so it is not tuned.
+fn weights<'a>(config: &'a mut EvalConfig, mirrored: &[bool]) -> Vec<Weight<'a>> {
+    let EvalConfig {
+        kingside_castle,
+        queenside_castle,
+        pawn_structure: pawns,
+        king_safety: safety,
+        activity,
+        pawn,
+        knight,
+        queen,
+        king,
+        bishop,
+        rook,
+    } = config;
+    let mut scalars = vec![
+        kingside_castle,
+        queenside_castle,
+        &mut pawns.doubled,
+        &mut pawns.isolated,
+        &mut pawns.backward,
+        &mut pawns.connected,
+    ];
+    scalars.extend(&mut pawns.passed);
+    scalars.extend([
+        &mut pawns.blocked_passed,
+        &mut safety.shield,
+        &mut safety.shield_far,
+        &mut safety.storm,
+        &mut safety.open_file,
+        &mut safety.half_open_file,
+    ]);
+    for pieces in [&mut safety.attacks, &mut activity.mobility] {
+        scalars.extend([
+            &mut pieces.knight,
+            &mut pieces.bishop,
+            &mut pieces.rook,
+            &mut pieces.queen,
+        ]);
+    }
+    scalars.extend([
+        &mut activity.rook_open_file,
+        &mut activity.rook_half_open_file,
+        &mut activity.bishop_pair,
+        &mut activity.knight_outpost,
+        &mut activity.defended,
+        &mut activity.attacked,
+    ]);
+    let mut weights: Vec<Weight> = scalars
+        .into_iter()
+        .map(|weight| (weight, None))
+        .collect();
+    let tables = [pawn, knight, bishop, rook, queen, king]
+        .into_iter()
+        .flat_map(|PieceTables { midgame, endgame }| [midgame, endgame]);
+    for (table, &mirrored) in tables.zip(mirrored) {
+        let white = table
+            .white
+            .iter_mut()
+            .flatten();
+        if mirrored {
+            // the black table is the white one with the ranks reversed
+            let black = table
+                .black
+                .iter_mut()
+                .rev()
+                .flatten();
+            weights.extend(
+                white
+                    .zip(black)
+                    .map(|(white, black)| (white, Some(black))),
+            );
+        } else {
+            let black = table
+                .black
+                .iter_mut()
+                .flatten();
+            weights.extend(
+                white
+                    .chain(black)
+                    .map(|weight| (weight, None)),
+            );
+        }
+    }
+    weights
+}
+
+#[test]
+fn parse_formats() {
+    let text = "\
+# zurichess style
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1/2-1/2\";
+4k3/8/8/8/8/8/8/4K2R w K - [1.0]
+fen,result
+4k3/8/8/8/8/8/8/4K2r w - - 0 1,0-1
+4k3/8/8/8/8/8/8/4K2R w K - id \"a, b\"; c9 \"1-0\"; c1 \"x,y\";
+4k3/8/8/8/8/8/8/4K2R  w K  -   c9 \"0-1\";
+";
+    let results: Vec<_> = parse_samples(text)
+        .unwrap()
+        .iter()
+        .map(|sample| sample.result)
+        .collect();
+    assert_eq!(results, [0.5, 1.0, 0.0, 1.0, 0.0]);
+    assert_eq!(
+        parse_samples("4k3/8/8/8/8/8/8/4K2R w K -").unwrap_err(),
+        SampleError::MissingResult { line: 1 }
+    );
+}
+
+#[test]
+fn tuning_lowers_the_error() {
+    let text = "\
+4k3/pppp4/8/8/8/8/PPPP4/Q3K3 w - - [1.0]
+q3k3/pppp4/8/8/8/8/PPPP4/4K3 w - - [0.0]
+4k3/pppp4/8/8/8/8/PPPP4/N3K3 w - - [0.5]
+4k3/pppp4/8/8/8/8/PPPP4/4KN2 w - - [0.5]
+";
+    let samples = parse_samples(text).unwrap();
+    let config = EvalConfig::default();
+    let k = fit_k(&samples, &config);
+    let options = TuneOptions {
+        iterations: 1,
+        ..Default::default()
+    };
+    let mut errors = vec![];
+    let tuned = tune(&samples, &config, options, |_, error| errors.push(error));
+    assert_eq!(errors.len(), 1);
+    assert!(error(&samples, &tuned, k) < error(&samples, &config, k));
+    // the default tables only give white, so black still mirrors it
+    assert!(mirrored(&tuned)
+        .iter()
+        .all(|&mirrored| mirrored));
+}
@@ -0,0 +1,474 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::allocate;
+use crate::game::Game;
+use crate::minimax::{Limits, SearchResult, MATE};
+use crate::piece::Color;
+use crate::search::search;
+use crate::settings::EvalConfig;
+use crate::stats::{IterationStats, SearchObserver};
+use crate::tt::{self, TranspositionTable};
+
+/// Talks the Universal Chess Interface over `input` and `output` until
+/// `quit` or the end of the input. The input is read on its own thread
+/// and the search runs on another, so `stop` ends the search and the info
+/// of each iteration is written as soon as the iteration is done.
+/// Commands other than `stop`, `ponderhit` and `isready` wait for the
+/// search to finish.
+pub fn run(
+    config: &EvalConfig,
+    limits: Limits,
+    input: impl BufRead + Send,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    // the transposition table is kept from one move to the next
+    let mut limits = Limits {
+        tt: Some(
+            limits
+                .tt
+                .unwrap_or_else(|| Arc::new(TranspositionTable::new(tt::DEFAULT_SIZE))),
+        ),
+        ..limits
+    };
+    let mut game = Game::default();
+    let (sender, events) = mpsc::channel();
+    std::thread::scope(|scope| {
+        let reader = sender.clone();
+        scope.spawn(move || {
+            for line in input.lines() {
+                let last = !matches!(&line, Ok(line) if line.trim() != "quit");
+                // the receiver is gone once the session is over
+                if reader
+                    .send(Event::Input(line))
+                    .is_err()
+                    || last
+                {
+                    return;
+                }
+            }
+            let _ = reader.send(Event::Input(Ok("quit".into())));
+        });
+        let mut searching: Option<Search> = None;
+        let mut queued = VecDeque::new();
+        let mut session = || loop {
+            let event = match queued.pop_front() {
+                Some(line) if searching.is_none() => Event::Input(Ok(line)),
+                line => {
+                    queued.extend(line);
+                    events
+                        .recv()
+                        .expect("the session keeps a sender")
+                }
+            };
+            let line = match event {
+                Event::Input(line) => line?,
+                Event::Info(stats) => {
+                    iteration(&stats, &mut output)?;
+                    output.flush()?;
+                    continue;
+                }
+                Event::Done(result) => {
+                    let running = searching
+                        .as_mut()
+                        .expect("a search is running");
+                    // after `go infinite` the best move waits for `stop`
+                    if running.infinite {
+                        running.result = Some(result);
+                    } else {
+                        report(&result, &mut output)?;
+                        output.flush()?;
+                        searching = None;
+                    }
+                    continue;
+                }
+            };
+            let mut words = line.split_whitespace();
+            if let Some(running) = &mut searching {
+                match words.next() {
+                    Some("isready") => writeln!(output, "readyok")?,
+                    // an infinite search only ends with one of these
+                    Some(command @ ("stop" | "ponderhit" | "quit"))
+                        if running.infinite || command == "stop" =>
+                    {
+                        if command == "quit" {
+                            queued.push_back(line.clone());
+                        }
+                        // after `ponderhit` the search goes on by its limits
+                        if command != "ponderhit" {
+                            running
+                                .stop
+                                .store(true, Ordering::Relaxed);
+                        }
+                        running.infinite = false;
+                        if let Some(result) = running.result.take() {
+                            report(&result, &mut output)?;
+                            searching = None;
+                        }
+                    }
+                    _ => queued.push_back(line),
+                }
+                output.flush()?;
+                continue;
+            }
+            match words.next() {
+                Some("uci") => {
+                    writeln!(output, "id name chess {}", env!("CARGO_PKG_VERSION"))?;
+                    writeln!(output, "id author the chess authors")?;
+                    writeln!(
+                        output,
+                        "option name MultiPV type spin default 1 min 1 max 256"
+                    )?;
+                    writeln!(
+                        output,
+                        "option name Threads type spin default 1 min 1 max 256"
+                    )?;
+                    writeln!(
+                        output,
+                        "option name Hash type spin default 1 min 1 max 4096"
+                    )?;
+                    writeln!(output, "uciok")?;
+                }
+                Some("isready") => writeln!(output, "readyok")?,
+                Some("setoption") => {
+                    if let Err(err) = set_option(&line, &mut limits) {
+                        writeln!(output, "info string {err}")?;
+                    }
+                }
+                Some("ucinewgame") => {
+                    game = Game::default();
+                    if let Some(tt) = &limits.tt {
+                        tt.clear();
+                    }
+                }
+                Some("position") => match position(words) {
+                    Some(position) => game = position,
+                    None => writeln!(output, "info string invalid position: {line}")?,
+                },
+                Some("go") => {
+                    let infinite = words
+                        .clone()
+                        .any(|word| word == "infinite" || word == "ponder");
+                    let stop = Arc::new(AtomicBool::new(false));
+                    let limits = Limits {
+                        observer: Some(Arc::new(Info(Mutex::new(sender.clone())))),
+                        stop: Some(stop.clone()),
+                        ..go(words, &limits, game.turn())
+                    };
+                    let (board, turn) = (*game.board(), game.turn());
+                    let done = sender.clone();
+                    scope.spawn(move || {
+                        let result = search(board, turn, limits, config);
+                        // the receiver is gone if the output failed
+                        let _ = done.send(Event::Done(result));
+                    });
+                    searching = Some(Search {
+                        stop,
+                        infinite,
+                        result: None,
+                    });
+                }
+                Some("quit") => return Ok(()),
+                // unknown commands are ignored, as the protocol asks
+                _ => (),
+            }
+            output.flush()?;
+        };
+        let result = session();
+        // a search left running when the session ends is stopped
+        if let Some(running) = &searching {
+            running
+                .stop
+                .store(true, Ordering::Relaxed);
+        }
+        result
+    })
+}
+
+/// What the threads of a session tell the loop answering the commands.
+enum Event {
+    /// A line of the input.
+    Input(std::io::Result<String>),
+    /// The statistics of an iteration of the search.
+    Info(IterationStats),
+    /// The result of the search.
+    Done(SearchResult),
+}
+
+/// The search of a `go` command, while it runs or waits for `stop`.
+struct Search {
+    stop: Arc<AtomicBool>,
+    /// Whether the search is `infinite` or `ponder`, so its best move is
+    /// only sent after `stop` or `ponderhit`.
+    infinite: bool,
+    /// The result of an infinite search that finished before `stop`.
+    result: Option<SearchResult>,
+}
+
+fn set_option(line: &str, limits: &mut Limits) -> Result<(), String> {
+    let rest = line
+        .split_once("name")
+        .map(|(_, rest)| rest)
+        .ok_or("missing option name")?;
+    let (name, value) = rest
+        .split_once("value")
+        .unwrap_or((rest, ""));
+    let (name, value) = (name.trim(), value.trim());
+    match name.to_lowercase().as_str() {
+        "multipv" => {
+            limits.multi_pv = value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid MultiPV {value:?}"))?
+                .clamp(1, 256);
+        }
+        "threads" => {
+            limits.threads = value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid Threads {value:?}"))?
+                .clamp(1, 256);
+        }
+        "hash" => {
+            let megabytes = value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid Hash {value:?}"))?
+                .clamp(1, 4096);
+            // the old table goes first, so both are never allocated at once
+            limits.tt = None;
+            // an entry of the transposition table takes 16 bytes
+            limits.tt = Some(Arc::new(TranspositionTable::new(megabytes << 16)));
+        }
+        _ => return Err(format!("unknown option {name:?}")),
+    }
+    Ok(())
+}
+
+/// Parses `startpos` or `fen <fen>`, followed by `moves` and the moves
+/// played from there.
+fn position<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<Game> {
+    let mut game = match words.next()? {
+        "startpos" => Game::default(),
+        "fen" => {
+            let fen: Vec<_> = words
+                .by_ref()
+                .take_while(|&word| word != "moves")
+                .collect();
+            let mut game = Game::from_fen(&fen.join(" ")).ok()?;
+            // `take_while` already consumed the `moves` keyword
+            for r#move in words {
+                game.push_san(r#move)?;
+            }
+            return Some(game);
+        }
+        _ => return None,
+    };
+    if words.next() == Some("moves") {
+        for r#move in words {
+            game.push_san(r#move)?;
+        }
+    }
+    Some(game)
+}
+
+/// The limits of a `go` command. Without any, the search goes to the
+/// default depth.
+fn go<'a>(words: impl Iterator<Item = &'a str>, limits: &Limits, turn: Color) -> Limits {
+    let mut limits = limits.clone();
+    let words: Vec<_> = words.collect();
+    let value = |name: &str| -> Option<u64> {
+        let index = words
+            .iter()
+            .position(|&word| word == name)?;
+        words
+            .get(index + 1)?
+            .parse()
+            .ok()
+    };
+    let millis = |name| value(name).map(Duration::from_millis);
+    if let Some(depth) = value("depth") {
+        limits.depth = depth as u32;
+    }
+    if let Some(time) = millis("movetime") {
+        limits.depth = 256;
+        limits.time = Some(time);
+    }
+    let (time, increment) = match turn {
+        Color::White => ("wtime", "winc"),
+        Color::Black => ("btime", "binc"),
+    };
+    if let Some(remaining) = millis(time) {
+        limits.depth = value("depth").unwrap_or(256) as u32;
+        limits.clock = Some(allocate(
+            remaining,
+            value("movestogo").map(|moves| moves as u32),
+            millis(increment).unwrap_or_default(),
+            Duration::ZERO,
+        ));
+    }
+    limits
+}
+
+/// Sends the statistics of each iteration to the thread writing the output.
+#[derive(Debug)]
+struct Info(Mutex<Sender<Event>>);
+
+impl SearchObserver for Info {
+    fn iteration(&self, stats: &IterationStats) {
+        // the receiver only stops listening when the output fails
+        let _ = self
+            .0
+            .lock()
+            .expect("the sender is not poisoned")
+            .send(Event::Info(stats.clone()));
+    }
+}
+
+fn iteration(stats: &IterationStats, output: &mut impl Write) -> std::io::Result<()> {
+    let pv: Vec<_> = stats
+        .pv
+        .iter()
+        .map(|r#move| r#move.to_string())
+        .collect();
+    writeln!(
+        output,
+        "info depth {} seldepth {} score {} nodes {} nps {} time {} pv {}",
+        stats.depth,
+        stats.seldepth,
+        score(stats.score),
+        stats.nodes,
+        stats.nps(),
+        stats.elapsed.as_millis(),
+        pv.join(" ")
+    )
+}
+
+fn report(result: &SearchResult, output: &mut impl Write) -> std::io::Result<()> {
+    for (i, line) in result
+        .lines
+        .iter()
+        .enumerate()
+    {
+        let pv: Vec<_> = line
+            .pv
+            .iter()
+            .map(|r#move| r#move.to_string())
+            .collect();
+        writeln!(
+            output,
+            "info depth {} multipv {} score {} nodes {} pv {}",
+            result.depth,
+            i + 1,
+            score(line.score),
+            result.nodes,
+            pv.join(" ")
+        )?;
+    }
+    match result.best_move {
+        Some(r#move) => writeln!(output, "bestmove {move}"),
+        None => writeln!(output, "bestmove 0000"),
+    }
+}
+
+/// The score in centipawns, or in moves to mate.
+fn score(score: f32) -> String {
+    let plies = MATE - score.abs();
+    if plies < 500.0 {
+        let moves = (plies.round() as i32 + 1) / 2;
+        format!("mate {}", if score > 0.0 { moves } else { -moves })
+    } else {
+        format!("cp {}", (score * 100.0).round() as i32)
+    }
+}
+
+#[test]
+fn uci_session() {
+    let input = "\
+uci
+isready
+setoption name MultiPV value 3
+position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1
+go depth 2
+position startpos moves e2e4 e7e5
+go depth 1
+quit
+";
+    let mut output = vec![];
+    run(
+        &EvalConfig::default(),
+        Limits::default(),
+        input.as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("uciok\nreadyok\n"));
+    assert!(output.contains("info depth 2 multipv 1 score mate 1 "));
+    assert!(output.contains(" multipv 3 "));
+    assert!(output.contains("info depth 1 seldepth 1 score "));
+    assert!(output.contains("bestmove a1a8\n"));
+    assert_eq!(
+        output
+            .lines()
+            .filter(|line| line.starts_with("bestmove"))
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn stop_infinite_search() {
+    use std::io::BufReader;
+    let (input, mut commands) = std::io::pipe().unwrap();
+    let (replies, output) = std::io::pipe().unwrap();
+    let session = std::thread::spawn(move || {
+        run(
+            &EvalConfig::default(),
+            Limits::default(),
+            BufReader::new(input),
+            output,
+        )
+    });
+    let mut replies = BufReader::new(replies).lines();
+    let mut until = |prefix: &str| -> Vec<String> {
+        let mut lines = vec![];
+        for line in replies.by_ref() {
+            let line = line.unwrap();
+            lines.push(line.clone());
+            if line.starts_with(prefix) {
+                return lines;
+            }
+        }
+        panic!("no {prefix:?} in {lines:?}");
+    };
+    // the mate is found at once, but the best move waits for `stop`
+    writeln!(commands, "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    writeln!(commands, "go infinite").unwrap();
+    until("info depth 1 ");
+    std::thread::sleep(Duration::from_millis(50));
+    writeln!(commands, "isready").unwrap();
+    assert!(!until("readyok")
+        .iter()
+        .any(|line| line.starts_with("bestmove")));
+    writeln!(commands, "stop").unwrap();
+    assert_eq!(
+        until("bestmove")
+            .last()
+            .unwrap(),
+        "bestmove a1a8"
+    );
+
+    // without `stop` this search would go on to depth 256
+    writeln!(commands, "position startpos").unwrap();
+    writeln!(commands, "go infinite").unwrap();
+    until("info depth 2 ");
+    writeln!(commands, "stop").unwrap();
+    until("bestmove");
+    writeln!(commands, "quit").unwrap();
+    session
+        .join()
+        .unwrap()
+        .unwrap();
+}